@@ -0,0 +1,116 @@
+/*
+The erased output is a trait object of the io flavor's traits, so like the syncable streams every
+io flavor gets its own BoxUpgrader and BoxedAsyncStream.
+*/
+
+macro_rules! boxed {
+    () => {
+        use std::io;
+        use std::pin::Pin;
+
+        use async_trait::async_trait;
+
+        use crate::upgradable::Upgrader;
+
+        pub trait AsyncReadWrite: AsyncRead + AsyncWrite {}
+
+        impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite + ?Sized {}
+
+        pub type BoxedAsyncStream = Pin<Box<dyn AsyncReadWrite + Send>>;
+
+        pub struct BoxUpgrader<S> {
+            inner: Box<dyn Upgrader<S, Output = BoxedAsyncStream> + Send>,
+        }
+
+        impl<S> BoxUpgrader<S>
+        where
+            S: Send + 'static,
+        {
+            pub fn new<U>(upgrader: U) -> Self
+            where
+                U: Upgrader<S> + Send + 'static,
+                U::Output: AsyncRead + AsyncWrite + Send + 'static,
+            {
+                Self {
+                    inner: Box::new(ErasedUpgrader(upgrader)),
+                }
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for BoxUpgrader<S>
+        where
+            S: Send + 'static,
+        {
+            type Output = BoxedAsyncStream;
+            async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+                self.inner.upgrade(stream).await
+            }
+            fn upgrade_required(&self) -> bool {
+                self.inner.upgrade_required()
+            }
+            fn recover_stream(&mut self) -> Option<S> {
+                self.inner.recover_stream()
+            }
+        }
+
+        struct ErasedUpgrader<U>(U);
+
+        #[async_trait]
+        impl<S, U> Upgrader<S> for ErasedUpgrader<U>
+        where
+            S: Send + 'static,
+            U: Upgrader<S> + Send,
+            U::Output: AsyncRead + AsyncWrite + Send + 'static,
+        {
+            type Output = BoxedAsyncStream;
+            async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+                let stream = self.0.upgrade(stream).await?;
+                Ok(Box::pin(stream))
+            }
+            fn upgrade_required(&self) -> bool {
+                self.0.upgrade_required()
+            }
+            fn recover_stream(&mut self) -> Option<S> {
+                self.0.recover_stream()
+            }
+        }
+
+        // Steps built at runtime. Like the hops of ChainedUpgrader every step runs, one that isn't
+        // required has to pass the stream through.
+        #[async_trait]
+        impl Upgrader<BoxedAsyncStream> for Vec<BoxUpgrader<BoxedAsyncStream>> {
+            type Output = BoxedAsyncStream;
+            async fn upgrade(&mut self, mut stream: BoxedAsyncStream) -> io::Result<Self::Output> {
+                for upgrader in self.iter_mut() {
+                    stream = upgrader.upgrade(stream).await?;
+                }
+                Ok(stream)
+            }
+            fn upgrade_required(&self) -> bool {
+                self.iter().any(|upgrader| upgrader.upgrade_required())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    boxed!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    boxed!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    boxed!();
+}
@@ -0,0 +1,113 @@
+use std::io;
+
+use async_trait::async_trait;
+
+use crate::gradable::Downgrader;
+use crate::upgradable::Upgrader;
+use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+/*
+Runs first then second, e.g. proxy TLS and then origin TLS.
+
+The chain is required when either half is. A hop's output is the next hop's input, so a hop
+that isn't required still runs and has to pass the stream through, like () and None do.
+*/
+pub struct ChainedUpgrader<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> ChainedUpgrader<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn second_mut(&mut self) -> &mut B {
+        &mut self.second
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+#[async_trait]
+impl<S, A, B> Upgrader<S> for ChainedUpgrader<A, B>
+where
+    S: Send + 'static,
+    A: Upgrader<S> + Send,
+    A::Output: Send + 'static,
+    B: Upgrader<A::Output> + Send,
+{
+    type Output = B::Output;
+    async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+        let stream = self.first.upgrade(stream).await?;
+        self.second.upgrade(stream).await
+    }
+    fn upgrade_required(&self) -> bool {
+        self.first.upgrade_required() || self.second.upgrade_required()
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        self.first.recover_stream()
+    }
+}
+
+#[async_trait]
+impl<S, A, B> Downgrader<S> for ChainedUpgrader<A, B>
+where
+    S: Send + 'static,
+    A: Downgrader<S> + Send,
+    A::Output: Send + 'static,
+    B: Downgrader<A::Output> + Send,
+    B::Output: Send,
+{
+    async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+        let output = self.second.downgrade(output).await?;
+        self.first.downgrade(output).await
+    }
+    fn downgrade_required(&self) -> bool {
+        self.first.downgrade_required() || self.second.downgrade_required()
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        self.second.recover_output()
+    }
+}
+
+impl<S, A, B> UpgraderExtRefer<S> for ChainedUpgrader<A, B>
+where
+    S: Send + 'static,
+    A: UpgraderExtRefer<S> + Send,
+    A::Output: Send + 'static,
+    B: UpgraderExtRefer<A::Output> + Send,
+{
+    fn get_ref(output: &Self::Output) -> &S {
+        A::get_ref(B::get_ref(output))
+    }
+    fn get_mut(output: &mut Self::Output) -> &mut S {
+        A::get_mut(B::get_mut(output))
+    }
+}
+
+impl<S, A, B> UpgraderExtIntoStream<S> for ChainedUpgrader<A, B>
+where
+    S: Send + 'static,
+    A: UpgraderExtIntoStream<S> + Send,
+    A::Output: Send + 'static,
+    B: UpgraderExtIntoStream<A::Output> + Send,
+{
+    fn into_stream(output: Self::Output) -> io::Result<S> {
+        A::into_stream(B::into_stream(output)?)
+    }
+}
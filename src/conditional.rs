@@ -0,0 +1,245 @@
+use std::io;
+
+use async_trait::async_trait;
+use either::Either;
+
+use crate::gradable::Downgrader;
+use crate::tls::{TlsClientUpgrader, TlsServerUpgrader};
+use crate::unionable::UnionableAsyncStream;
+use crate::upgradable::Upgrader;
+use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+// The output wasn't made by this upgrader, e.g. with_upgraded_stream_and_upgrader got a mismatched
+// pair.
+fn mismatched_output() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "output doesn't match the upgrader",
+    )
+}
+
+//
+// None means no upgrade required, like ().
+//
+#[async_trait]
+impl<S, U> Upgrader<S> for Option<U>
+where
+    S: Send + 'static,
+    U: Upgrader<S> + Send,
+{
+    type Output = UnionableAsyncStream<U::Output, S>;
+    async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+        match self {
+            Some(upgrader) => upgrader
+                .upgrade(stream)
+                .await
+                .map(UnionableAsyncStream::one),
+            None => Ok(UnionableAsyncStream::the_other(stream)),
+        }
+    }
+    fn upgrade_required(&self) -> bool {
+        match self {
+            Some(upgrader) => upgrader.upgrade_required(),
+            None => false,
+        }
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        match self {
+            Some(upgrader) => upgrader.recover_stream(),
+            None => None,
+        }
+    }
+}
+
+#[async_trait]
+impl<S, U> Downgrader<S> for Option<U>
+where
+    S: Send + 'static,
+    U: Downgrader<S> + Send,
+    U::Output: Send,
+{
+    async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+        match (self, output.into_inner()) {
+            (Some(upgrader), Either::Left(output)) => upgrader.downgrade(output).await,
+            (_, Either::Right(stream)) => Ok(stream),
+            (None, Either::Left(_)) => Err(mismatched_output()),
+        }
+    }
+    fn downgrade_required(&self) -> bool {
+        match self {
+            Some(upgrader) => upgrader.downgrade_required(),
+            None => false,
+        }
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        match self {
+            Some(upgrader) => upgrader.recover_output().map(UnionableAsyncStream::one),
+            None => None,
+        }
+    }
+}
+
+impl<S, U> UpgraderExtRefer<S> for Option<U>
+where
+    S: Send + 'static,
+    U: UpgraderExtRefer<S> + Send,
+{
+    fn get_ref(output: &Self::Output) -> &S {
+        match output.get_ref() {
+            Either::Left(output) => U::get_ref(output),
+            Either::Right(stream) => stream,
+        }
+    }
+    fn get_mut(output: &mut Self::Output) -> &mut S {
+        match output.get_mut() {
+            Either::Left(output) => U::get_mut(output),
+            Either::Right(stream) => stream,
+        }
+    }
+}
+
+impl<S, U> UpgraderExtIntoStream<S> for Option<U>
+where
+    S: Send + 'static,
+    U: UpgraderExtIntoStream<S> + Send,
+{
+    fn into_stream(output: Self::Output) -> io::Result<S> {
+        match output.into_inner() {
+            Either::Left(output) => U::into_stream(output),
+            Either::Right(stream) => Ok(stream),
+        }
+    }
+}
+
+impl<S, U> TlsClientUpgrader<S> for Option<U>
+where
+    S: Send + 'static,
+    U: TlsClientUpgrader<S> + Send,
+{
+}
+
+impl<S, U> TlsServerUpgrader<S> for Option<U>
+where
+    S: Send + 'static,
+    U: TlsServerUpgrader<S> + Send,
+{
+}
+
+//
+//
+//
+#[async_trait]
+impl<S, A, B> Upgrader<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: Upgrader<S> + Send,
+    B: Upgrader<S> + Send,
+{
+    type Output = UnionableAsyncStream<A::Output, B::Output>;
+    async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+        match self {
+            Either::Left(upgrader) => upgrader
+                .upgrade(stream)
+                .await
+                .map(UnionableAsyncStream::one),
+            Either::Right(upgrader) => upgrader
+                .upgrade(stream)
+                .await
+                .map(UnionableAsyncStream::the_other),
+        }
+    }
+    fn upgrade_required(&self) -> bool {
+        match self {
+            Either::Left(upgrader) => upgrader.upgrade_required(),
+            Either::Right(upgrader) => upgrader.upgrade_required(),
+        }
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        match self {
+            Either::Left(upgrader) => upgrader.recover_stream(),
+            Either::Right(upgrader) => upgrader.recover_stream(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, A, B> Downgrader<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: Downgrader<S> + Send,
+    A::Output: Send,
+    B: Downgrader<S> + Send,
+    B::Output: Send,
+{
+    async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+        match (self, output.into_inner()) {
+            (Either::Left(upgrader), Either::Left(output)) => upgrader.downgrade(output).await,
+            (Either::Right(upgrader), Either::Right(output)) => upgrader.downgrade(output).await,
+            _ => Err(mismatched_output()),
+        }
+    }
+    fn downgrade_required(&self) -> bool {
+        match self {
+            Either::Left(upgrader) => upgrader.downgrade_required(),
+            Either::Right(upgrader) => upgrader.downgrade_required(),
+        }
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        match self {
+            Either::Left(upgrader) => upgrader.recover_output().map(UnionableAsyncStream::one),
+            Either::Right(upgrader) => upgrader
+                .recover_output()
+                .map(UnionableAsyncStream::the_other),
+        }
+    }
+}
+
+impl<S, A, B> UpgraderExtRefer<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: UpgraderExtRefer<S> + Send,
+    B: UpgraderExtRefer<S> + Send,
+{
+    fn get_ref(output: &Self::Output) -> &S {
+        match output.get_ref() {
+            Either::Left(output) => A::get_ref(output),
+            Either::Right(output) => B::get_ref(output),
+        }
+    }
+    fn get_mut(output: &mut Self::Output) -> &mut S {
+        match output.get_mut() {
+            Either::Left(output) => A::get_mut(output),
+            Either::Right(output) => B::get_mut(output),
+        }
+    }
+}
+
+impl<S, A, B> UpgraderExtIntoStream<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: UpgraderExtIntoStream<S> + Send,
+    B: UpgraderExtIntoStream<S> + Send,
+{
+    fn into_stream(output: Self::Output) -> io::Result<S> {
+        match output.into_inner() {
+            Either::Left(output) => A::into_stream(output),
+            Either::Right(output) => B::into_stream(output),
+        }
+    }
+}
+
+impl<S, A, B> TlsClientUpgrader<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: TlsClientUpgrader<S> + Send,
+    B: TlsClientUpgrader<S> + Send,
+{
+}
+
+impl<S, A, B> TlsServerUpgrader<S> for Either<A, B>
+where
+    S: Send + 'static,
+    A: TlsServerUpgrader<S> + Send,
+    B: TlsServerUpgrader<S> + Send,
+{
+}
@@ -1,28 +1,35 @@
+use std::future::Future;
 use std::io;
-use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use async_trait::async_trait;
-use futures_x_io::{AsyncRead, AsyncWrite};
 
-use crate::upgradable::{Inner, UpgradableAsyncStream, Upgrader};
+use crate::upgradable::{poll_transition, Inner, UpgradableAsyncStream, Upgrader};
+use crate::upgradable_error::UpgradeError;
 
 pub type GradableAsyncStream<S, SU> = UpgradableAsyncStream<S, SU>;
 
 #[async_trait]
 pub trait Downgrader<S>: Upgrader<S> {
     async fn downgrade(&mut self, output: Self::Output) -> io::Result<S>;
+    /// When false, `downgrade` is still called inside a chain and should pass the output through.
     fn downgrade_required(&self) -> bool {
         true
     }
+    /// Called after a failed `downgrade`, gives back the output if the downgrader kept it.
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        None
+    }
 }
 
 #[async_trait]
 impl<S> Downgrader<S> for ()
 where
-    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
 {
-    async fn downgrade(&mut self, _: <Self as Upgrader<S>>::Output) -> io::Result<S> {
-        unreachable!()
+    async fn downgrade(&mut self, output: <Self as Upgrader<S>>::Output) -> io::Result<S> {
+        Ok(output)
     }
     fn downgrade_required(&self) -> bool {
         false
@@ -39,30 +46,62 @@ where
         }
     }
 
+    pub fn is_downgrading(&self) -> bool {
+        matches!(self.inner, Inner::Downgrading(_))
+    }
+
     pub fn downgrade_required(&self) -> bool {
         match &self.inner {
-            Inner::Pending(_, _) => false,
             Inner::Upgraded(_, grader) => grader.downgrade_required(),
-            Inner::None => panic!("never"),
+            _ => false,
         }
     }
+}
 
-    pub async fn downgrade(&mut self) -> io::Result<()> {
-        match mem::replace(&mut self.inner, Inner::None) {
-            Inner::Pending(_, _) => Err(io::Error::new(io::ErrorKind::Other, "not allow")),
-            Inner::Upgraded(stream, mut grader) => {
-                if !grader.downgrade_required() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "downgrade not required",
-                    ));
-                }
+impl<S, SU> GradableAsyncStream<S, SU>
+where
+    SU: Upgrader<S> + Downgrader<S> + Send + 'static,
+    SU::Output: Send + 'static,
+{
+    /*
+    Like upgrade, the in-flight downgrade is kept in the stream and resumed by the next call to
+    downgrade (or poll_downgrade).
+    */
+    pub fn downgrade(&mut self) -> DowngradeFuture<'_, S, SU> {
+        DowngradeFuture { stream: self }
+    }
 
-                let stream = grader.downgrade(stream).await?;
-                self.inner = Inner::Pending(stream, grader);
-                Ok(())
-            }
-            Inner::None => panic!("never"),
-        }
+    pub fn poll_downgrade(&mut self, cx: &mut Context) -> Poll<Result<(), UpgradeError>> {
+        self.inner.poll_downgrade_with(
+            cx,
+            |grader| grader.downgrade_required(),
+            |mut grader, output| {
+                Box::pin(async move {
+                    let ret = grader.downgrade(output).await;
+                    (grader, ret)
+                })
+            },
+            poll_transition,
+            |grader| grader.recover_output(),
+        )
+    }
+}
+
+pub struct DowngradeFuture<'a, S, SU>
+where
+    SU: Upgrader<S>,
+{
+    stream: &'a mut GradableAsyncStream<S, SU>,
+}
+
+impl<'a, S, SU> Future for DowngradeFuture<'a, S, SU>
+where
+    SU: Upgrader<S> + Downgrader<S> + Send + 'static,
+    SU::Output: Send + 'static,
+{
+    type Output = Result<(), UpgradeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().stream.poll_downgrade(cx)
     }
 }
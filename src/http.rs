@@ -1,177 +1,213 @@
-use std::io::{self, SeekFrom};
-use std::pin::Pin;
-use std::task::{Context, Poll};
-
-use futures_x_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
 
 use crate::http_tunnel::HttpTunnelClientGrader;
+use crate::socks4::Socks4ProxyUpgrader;
+use crate::socks5::Socks5ProxyUpgrader;
 use crate::tls::TlsClientUpgrader;
 use crate::upgradable::{UpgradableAsyncStream, Upgrader};
 
 //
 //
 //
-pub enum HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-{
-    // curl http://httpbin.org/ip -v
-    Case1(S),
-
-    // curl https://httpbin.org/ip -v
-    Case2(<TU as Upgrader<S>>::Output),
-
-    // curl -x http://127.0.0.1:8118 http://httpbin.org/ip -v
-    Case3(S),
-
-    // curl -x http://127.0.0.1:8118 https://httpbin.org/ip -v
-    Case4(<TU as Upgrader<S>>::Output),
-
-    // curl -x https://proxy.lvh.me:9118 http://httpbin.org/ip -v
-    Case5(<HTTU as Upgrader<S>>::Output),
-
-    // curl -x https://proxy.lvh.me:9118 https://httpbin.org/ip -v --proxy-insecure
-    Case6(<TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output),
-
-    // curl -x socks5://127.0.0.1:1080 http://httpbin.org/ip -v
-    Case7,
-
-    // curl -x socks5://127.0.0.1:1080 https://httpbin.org/ip -v
-    Case8,
-
-    Never(HTG),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpProxyKind {
+    Http,
+    Https,
+    Socks5,
+    Socks4,
 }
 
-impl<S, HTTU, HTG, TU> HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-{
-    pub fn is_case1(&self) -> bool {
+impl fmt::Display for HttpProxyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            HttpClientInnerStream::Case1(_) => true,
-            _ => false,
+            Self::Http => write!(f, "http"),
+            Self::Https => write!(f, "https"),
+            Self::Socks5 => write!(f, "socks5"),
+            Self::Socks4 => write!(f, "socks4"),
         }
     }
+}
 
-    pub fn is_case2(&self) -> bool {
-        match self {
-            HttpClientInnerStream::Case2(_) => true,
-            _ => false,
-        }
+/*
+The path of a connection, in curl terms
+
+curl http://httpbin.org/ip -v
+    proxy None, origin_tls false
+curl https://httpbin.org/ip -v
+    proxy None, origin_tls true
+curl -x http://127.0.0.1:8118 https://httpbin.org/ip -v
+    proxy Some(Http), origin_tls true
+curl -x https://proxy.lvh.me:9118 http://httpbin.org/ip -v
+    proxy Some(Https), origin_tls false
+curl -x socks5://127.0.0.1:1080 https://httpbin.org/ip -v
+    proxy Some(Socks5), origin_tls true
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpTransport {
+    pub proxy: Option<HttpProxyKind>,
+    pub origin_tls: bool,
+}
+
+impl HttpTransport {
+    // Whether the origin is talked to over TLS, the proxy connection doesn't count.
+    pub fn is_tls(&self) -> bool {
+        self.origin_tls
     }
 
-    pub fn is_case3(&self) -> bool {
-        match self {
-            HttpClientInnerStream::Case3(_) => true,
-            _ => false,
-        }
+    pub fn via_proxy(&self) -> bool {
+        self.proxy.is_some()
     }
 
-    pub fn is_case4(&self) -> bool {
-        match self {
-            HttpClientInnerStream::Case4(_) => true,
-            _ => false,
-        }
+    pub fn proxy_kind(&self) -> Option<HttpProxyKind> {
+        self.proxy
     }
+}
 
-    pub fn is_case5(&self) -> bool {
-        match self {
-            HttpClientInnerStream::Case5(_) => true,
-            _ => false,
+// direct, direct+tls, http-proxy+tls, ...
+impl fmt::Display for HttpTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.proxy {
+            Some(kind) => write!(f, "{}-proxy", kind)?,
+            None => write!(f, "direct")?,
         }
+        if self.origin_tls {
+            write!(f, "+tls")?;
+        }
+        Ok(())
     }
+}
 
-    pub fn is_case6(&self) -> bool {
-        match self {
-            HttpClientInnerStream::Case6(_) => true,
-            _ => false,
+//
+//
+//
+pub struct HttpClientInnerStream<S, HTTU, HTG, TU, SCU = ()>
+where
+    HTTU: TlsClientUpgrader<S>,
+    HTG: HttpTunnelClientGrader<S>,
+    HTG: HttpTunnelClientGrader<HTTU::Output>,
+    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S>,
+    SCU: Upgrader<S, Output = S>,
+{
+    inner: Inner<S, HTTU, TU>,
+    transport: HttpTransport,
+    phantom: PhantomData<fn() -> (HTG, SCU)>,
+}
+
+pub(crate) enum Inner<S, HTTU, TU>
+where
+    HTTU: Upgrader<S>,
+    TU: Upgrader<HTTU::Output> + Upgrader<S>,
+{
+    // Direct, or tunneled through an http or socks proxy.
+    Plain(S),
+    Tls(<TU as Upgrader<S>>::Output),
+    // Tunneled through an https proxy.
+    HttpsProxy(<HTTU as Upgrader<S>>::Output),
+    TlsOverHttpsProxy(<TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output),
+}
+
+impl<S, HTTU, HTG, TU, SCU> HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+where
+    HTTU: TlsClientUpgrader<S>,
+    HTG: HttpTunnelClientGrader<S>,
+    HTG: HttpTunnelClientGrader<HTTU::Output>,
+    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S>,
+    SCU: Upgrader<S, Output = S>,
+{
+    fn with_inner(inner: Inner<S, HTTU, TU>, proxy: Option<HttpProxyKind>) -> Self {
+        let origin_tls = match &inner {
+            Inner::Plain(_) | Inner::HttpsProxy(_) => false,
+            Inner::Tls(_) | Inner::TlsOverHttpsProxy(_) => true,
+        };
+        Self {
+            inner,
+            transport: HttpTransport { proxy, origin_tls },
+            phantom: PhantomData,
         }
     }
+
+    pub fn transport(&self) -> HttpTransport {
+        self.transport
+    }
 }
 
-impl<S, HTTU, HTG, TU> HttpClientInnerStream<S, HTTU, HTG, TU>
+impl<S, HTTU, HTG, TU, SCU> HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
 where
-    S: AsyncRead + AsyncWrite + Unpin,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+    S: Send + 'static,
+    HTTU: TlsClientUpgrader<S> + Send + 'static,
+    HTTU::Output: Send + 'static,
+    HTG: HttpTunnelClientGrader<S> + Send + 'static,
+    <HTG as Upgrader<S>>::Output: Send + 'static,
+    HTG: HttpTunnelClientGrader<HTTU::Output>,
+    <HTG as Upgrader<HTTU::Output>>::Output: Send + 'static,
+    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Send + 'static,
+    SCU: Upgrader<S, Output = S> + Send + 'static,
 {
     pub async fn new(
         stream: S,
-        proxy: Option<HttpClientProxy<S, HTTU, HTG>>,
+        proxy: Option<HttpClientProxy<S, HTTU, HTG, SCU>>,
         tls_upgrader: Option<TU>,
     ) -> io::Result<Self> {
-        if let Some(proxy) = proxy {
-            match proxy.inner {
-                HttpClientProxyInner::Http(http_tunnel_grader) => {
-                    let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_grader);
+        let proxy = match proxy {
+            Some(proxy) => proxy.inner,
+            None => {
+                if let Some(tls_upgrader) = tls_upgrader {
+                    let mut stream = UpgradableAsyncStream::new(stream, tls_upgrader);
                     stream.upgrade().await?;
-                    stream.downgrade().await?;
-                    let stream = stream.try_into_stream()?;
-
-                    if let Some(tls_upgrader) = tls_upgrader {
-                        let mut stream = UpgradableAsyncStream::new(stream, tls_upgrader);
-                        stream.upgrade().await?;
-                        let stream = stream.try_into_upgraded_stream()?;
-                        return Ok(Self::Case4(stream));
-                    }
-                    return Ok(Self::Case3(stream));
+                    let stream = stream.try_into_upgraded_stream()?;
+                    return Ok(Self::with_inner(Inner::Tls(stream), None));
                 }
-                HttpClientProxyInner::Https(http_tunnel_tls_upgrader, http_tunnel_grader) => {
-                    let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_tls_upgrader);
+                return Ok(Self::with_inner(Inner::Plain(stream), None));
+            }
+        };
+
+        let (stream, kind) = match proxy {
+            HttpClientProxyInner::Http(http_tunnel_grader) => {
+                let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_grader);
+                stream.upgrade().await?;
+                stream.downgrade().await?;
+                (stream.try_into_stream()?, HttpProxyKind::Http)
+            }
+            HttpClientProxyInner::Https(http_tunnel_tls_upgrader, http_tunnel_grader) => {
+                let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_tls_upgrader);
+                stream.upgrade().await?;
+                let stream = stream.try_into_upgraded_stream()?;
+
+                let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_grader);
+                stream.upgrade().await?;
+                stream.downgrade().await?;
+                let stream = stream.try_into_stream()?;
+
+                if let Some(tls_upgrader) = tls_upgrader {
+                    let mut stream = UpgradableAsyncStream::new(stream, tls_upgrader);
                     stream.upgrade().await?;
                     let stream = stream.try_into_upgraded_stream()?;
-
-                    let mut stream = UpgradableAsyncStream::new(stream, http_tunnel_grader);
-                    stream.upgrade().await?;
-                    stream.downgrade().await?;
-                    let stream = stream.try_into_stream()?;
-
-                    if let Some(tls_upgrader) = tls_upgrader {
-                        let mut stream = UpgradableAsyncStream::new(stream, tls_upgrader);
-                        stream.upgrade().await?;
-                        let stream = stream.try_into_upgraded_stream()?;
-                        return Ok(Self::Case6(stream));
-                    }
-                    return Ok(Self::Case5(stream));
+                    return Ok(Self::with_inner(
+                        Inner::TlsOverHttpsProxy(stream),
+                        Some(HttpProxyKind::Https),
+                    ));
                 }
-                HttpClientProxyInner::Never(_) => panic!("never"),
+                return Ok(Self::with_inner(
+                    Inner::HttpsProxy(stream),
+                    Some(HttpProxyKind::Https),
+                ));
             }
-        }
+            HttpClientProxyInner::Socks(socks_upgrader, kind) => {
+                let mut stream = UpgradableAsyncStream::new(stream, socks_upgrader);
+                stream.upgrade().await?;
+                (stream.try_into_upgraded_stream()?, kind)
+            }
+        };
 
         if let Some(tls_upgrader) = tls_upgrader {
             let mut stream = UpgradableAsyncStream::new(stream, tls_upgrader);
             stream.upgrade().await?;
             let stream = stream.try_into_upgraded_stream()?;
-
-            return Ok(Self::Case2(stream));
+            return Ok(Self::with_inner(Inner::Tls(stream), Some(kind)));
         }
-
-        return Ok(Self::Case1(stream));
+        Ok(Self::with_inner(Inner::Plain(stream), Some(kind)))
     }
 }
 
@@ -180,144 +216,396 @@ where
 //
 macro_rules! case {
     ($value:expr, $pattern:pat => $result:expr) => {
-        match $value {
-            HttpClientInnerStream::Case1($pattern) => $result,
-            HttpClientInnerStream::Case2($pattern) => $result,
-            HttpClientInnerStream::Case3($pattern) => $result,
-            HttpClientInnerStream::Case4($pattern) => $result,
-            HttpClientInnerStream::Case5($pattern) => $result,
-            HttpClientInnerStream::Case6($pattern) => $result,
-            HttpClientInnerStream::Case7 => unreachable!(),
-            HttpClientInnerStream::Case8 => unreachable!(),
-            HttpClientInnerStream::Never(_) => panic!("never"),
+        match $value.inner {
+            Inner::Plain($pattern) => $result,
+            Inner::Tls($pattern) => $result,
+            Inner::HttpsProxy($pattern) => $result,
+            Inner::TlsOverHttpsProxy($pattern) => $result,
         }
     };
 }
 
-impl<S, HTTU, HTG, TU> AsyncWrite for HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-{
-    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_write(cx, buf))
+#[cfg(feature = "futures_io")]
+mod futures_io_impl {
+    use std::io::{self, SeekFrom};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+    use super::{HttpClientInnerStream, Inner};
+    use crate::http_tunnel::HttpTunnelClientGrader;
+    use crate::tls::TlsClientUpgrader;
+    use crate::upgradable::Upgrader;
+
+    impl<S, HTTU, HTG, TU, SCU> AsyncWrite for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_write(cx, buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_flush(cx))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_close(cx))
+        }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_flush(cx))
+    impl<S, HTTU, HTG, TU, SCU> AsyncRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_read(cx, buf))
+        }
     }
 
-    #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_close(cx))
+    impl<S, HTTU, HTG, TU, SCU> AsyncSeek for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            pos: SeekFrom,
+        ) -> Poll<io::Result<u64>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_seek(cx, pos))
+        }
     }
 
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_shutdown(cx))
+    impl<S, HTTU, HTG, TU, SCU> AsyncBufRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_fill_buf(cx))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).consume(amt))
+        }
     }
 }
 
-impl<S, HTTU, HTG, TU> AsyncRead for HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
-{
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context,
-        buf: &mut [u8],
-    ) -> Poll<io::Result<usize>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_read(cx, buf))
+#[cfg(feature = "tokio_io")]
+mod tokio_io_impl {
+    use std::io::{self, SeekFrom};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+    use super::{HttpClientInnerStream, Inner};
+    use crate::http_tunnel::HttpTunnelClientGrader;
+    use crate::tls::TlsClientUpgrader;
+    use crate::upgradable::Upgrader;
+
+    impl<S, HTTU, HTG, TU, SCU> AsyncWrite for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_write(cx, buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_flush(cx))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_shutdown(cx))
+        }
     }
-}
 
-impl<S, HTTU, HTG, TU> AsyncSeek for HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin + AsyncSeek,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
-        AsyncRead + AsyncWrite + Unpin + AsyncSeek,
-{
-    #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
-    fn poll_seek(self: Pin<&mut Self>, cx: &mut Context, pos: SeekFrom) -> Poll<io::Result<u64>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_seek(cx, pos))
+    impl<S, HTTU, HTG, TU, SCU> AsyncRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_read(cx, buf))
+        }
     }
 
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn start_seek(
-        self: Pin<&mut Self>,
-        cx: &mut Context,
-        position: SeekFrom,
-    ) -> Poll<io::Result<()>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).start_seek(cx, position))
+    impl<S, HTTU, HTG, TU, SCU> AsyncSeek for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn start_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            position: SeekFrom,
+        ) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).start_seek(cx, position))
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_complete(cx))
+        }
     }
 
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_complete(cx))
+    impl<S, HTTU, HTG, TU, SCU> AsyncBufRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_fill_buf(cx))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).consume(amt))
+        }
     }
 }
 
-impl<S, HTTU, HTG, TU> AsyncBufRead for HttpClientInnerStream<S, HTTU, HTG, TU>
-where
-    S: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-    HTTU: TlsClientUpgrader<S> + Unpin,
-    HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-    HTG: HttpTunnelClientGrader<S> + Unpin,
-    <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-    HTG: HttpTunnelClientGrader<HTTU::Output> + Unpin + AsyncBufRead,
-    <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-    TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
-    <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-    <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
-        AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
-{
-    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_fill_buf(cx))
+#[cfg(feature = "tokio1_io")]
+mod tokio1_io_impl {
+    use std::io::{self, SeekFrom};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio1::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    use super::{HttpClientInnerStream, Inner};
+    use crate::http_tunnel::HttpTunnelClientGrader;
+    use crate::tls::TlsClientUpgrader;
+    use crate::upgradable::Upgrader;
+
+    impl<S, HTTU, HTG, TU, SCU> AsyncWrite for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_write(cx, buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_flush(cx))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_shutdown(cx))
+        }
     }
 
-    fn consume(self: Pin<&mut Self>, amt: usize) {
-        case!(self.get_mut(), ref mut inner => Pin::new(inner).consume(amt))
+    impl<S, HTTU, HTG, TU, SCU> AsyncRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output: AsyncRead + AsyncWrite + Unpin,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_read(cx, buf))
+        }
+    }
+
+    impl<S, HTTU, HTG, TU, SCU> AsyncSeek for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncSeek,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).start_seek(position))
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_complete(cx))
+        }
+    }
+
+    impl<S, HTTU, HTG, TU, SCU> AsyncBufRead for HttpClientInnerStream<S, HTTU, HTG, TU, SCU>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTTU: TlsClientUpgrader<S> + Unpin,
+        HTTU::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<S>,
+        <HTG as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        HTG: HttpTunnelClientGrader<HTTU::Output>,
+        <HTG as Upgrader<HTTU::Output>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        TU: TlsClientUpgrader<HTTU::Output> + Upgrader<S> + Unpin,
+        <TU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        <TU as Upgrader<<HTTU as Upgrader<S>>::Output>>::Output:
+            AsyncRead + AsyncWrite + Unpin + AsyncBufRead,
+        SCU: Upgrader<S, Output = S>,
+    {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).poll_fill_buf(cx))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            case!(self.get_mut(), ref mut inner => Pin::new(inner).consume(amt))
+        }
     }
 }
 
 //
 //
 //
-pub struct HttpClientProxy<S, TU, HTG> {
-    inner: HttpClientProxyInner<S, TU, HTG>,
+pub struct HttpClientProxy<S, TU, HTG, SCU = ()> {
+    inner: HttpClientProxyInner<TU, HTG, SCU>,
+    phantom: PhantomData<fn() -> S>,
 }
 
-enum HttpClientProxyInner<S, TU, HTG> {
+pub(crate) enum HttpClientProxyInner<TU, HTG, SCU> {
     Http(HTG),
     Https(TU, HTG),
-    // TODO, socks5
-    #[allow(dead_code)]
-    Never(S),
+    Socks(SCU, HttpProxyKind),
+}
+
+impl<S, TU, HTG, SCU> HttpClientProxy<S, TU, HTG, SCU> {
+    pub(crate) fn with_inner(inner: HttpClientProxyInner<TU, HTG, SCU>) -> Self {
+        Self {
+            inner,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn kind(&self) -> HttpProxyKind {
+        match &self.inner {
+            HttpClientProxyInner::Http(_) => HttpProxyKind::Http,
+            HttpClientProxyInner::Https(_, _) => HttpProxyKind::Https,
+            HttpClientProxyInner::Socks(_, kind) => *kind,
+        }
+    }
 }
 
 impl<S, TU, HTG> HttpClientProxy<S, TU, HTG>
@@ -325,9 +613,7 @@ where
     HTG: HttpTunnelClientGrader<S>,
 {
     pub fn http(http_tunnel_grader: HTG) -> Self {
-        Self {
-            inner: HttpClientProxyInner::Http(http_tunnel_grader),
-        }
+        Self::with_inner(HttpClientProxyInner::Http(http_tunnel_grader))
     }
 }
 
@@ -337,8 +623,35 @@ where
     HTG: HttpTunnelClientGrader<TU::Output>,
 {
     pub fn https(tls_upgrader: TU, http_tunnel_grader: HTG) -> Self {
-        Self {
-            inner: HttpClientProxyInner::Https(tls_upgrader, http_tunnel_grader),
-        }
+        Self::with_inner(HttpClientProxyInner::Https(
+            tls_upgrader,
+            http_tunnel_grader,
+        ))
+    }
+}
+
+impl<S, SCU> HttpClientProxy<S, (), (), SCU>
+where
+    S: Send + 'static,
+    SCU: Socks5ProxyUpgrader<S>,
+{
+    pub fn socks5(socks5_upgrader: SCU) -> Self {
+        Self::with_inner(HttpClientProxyInner::Socks(
+            socks5_upgrader,
+            HttpProxyKind::Socks5,
+        ))
+    }
+}
+
+impl<S, SCU> HttpClientProxy<S, (), (), SCU>
+where
+    S: Send + 'static,
+    SCU: Socks4ProxyUpgrader<S>,
+{
+    pub fn socks4(socks4_upgrader: SCU) -> Self {
+        Self::with_inner(HttpClientProxyInner::Socks(
+            socks4_upgrader,
+            HttpProxyKind::Socks4,
+        ))
     }
 }
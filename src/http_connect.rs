@@ -0,0 +1,706 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use crate::io_util::{read_exact, write_all, AsyncIo};
+use crate::socks5::TargetAddr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConnectResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpConnectResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn headers<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl HttpConnectResponse {
+    pub fn new(status: u16, reason: impl Into<String>) -> Self {
+        Self {
+            version: "HTTP/1.1".to_owned(),
+            status,
+            reason: reason.into(),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    // CR, LF or NUL in the name or value fails the write with InvalidInput.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConnectRequest {
+    pub method: String,
+    pub authority: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpConnectRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // The authority as host and port, None when it has no port.
+    pub fn target(&self) -> Option<TargetAddr> {
+        let i = self.authority.rfind(':')?;
+        let (host, port) = (&self.authority[..i], &self.authority[i + 1..]);
+        let port = port.parse::<u16>().ok()?;
+        if host.starts_with('[') && host.ends_with(']') {
+            let ip = host[1..host.len() - 1].parse::<Ipv6Addr>().ok()?;
+            return Some(TargetAddr::Ip(SocketAddr::new(ip.into(), port)));
+        }
+        if host.is_empty() || host.contains(':') {
+            return None;
+        }
+        match host.parse::<IpAddr>() {
+            Ok(ip) => Some(TargetAddr::Ip(SocketAddr::new(ip, port))),
+            Err(_) => Some(TargetAddr::Domain(host.to_owned(), port)),
+        }
+    }
+}
+
+//
+//
+//
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HttpConnectError {
+    Status(HttpConnectResponse),
+    InvalidResponse,
+    HeaderTooLarge,
+    // Server side, the response the request was answered with.
+    Denied(HttpConnectResponse),
+    InvalidRequest,
+}
+
+impl fmt::Display for HttpConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(res) => write!(
+                f,
+                "unexpected status {} {}: {}",
+                res.status,
+                res.reason,
+                String::from_utf8_lossy(&res.body)
+            ),
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::HeaderTooLarge => write!(f, "header too large"),
+            Self::Denied(res) => write!(f, "denied with {} {}", res.status, res.reason),
+            Self::InvalidRequest => write!(f, "invalid request"),
+        }
+    }
+}
+
+impl error::Error for HttpConnectError {}
+
+impl From<HttpConnectError> for io::Error {
+    fn from(err: HttpConnectError) -> Self {
+        let kind = match &err {
+            HttpConnectError::Status(res) if res.status == 403 || res.status == 407 => {
+                io::ErrorKind::PermissionDenied
+            }
+            HttpConnectError::Denied(_) => io::ErrorKind::PermissionDenied,
+            HttpConnectError::InvalidResponse
+            | HttpConnectError::HeaderTooLarge
+            | HttpConnectError::InvalidRequest => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+//
+//
+//
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+// A CR, LF or NUL would end the line early and let the rest be read as more headers.
+fn check_field(field: &str) -> io::Result<()> {
+    if field.bytes().any(|b| matches!(b, b'\r' | b'\n' | b'\0')) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "CR, LF or NUL in a header line",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HttpConnectClient {
+    pub(crate) target: TargetAddr,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl HttpConnectClient {
+    pub(crate) fn new(target: TargetAddr) -> Self {
+        let headers = vec![("Host".to_owned(), target.to_string())];
+        Self { target, headers }
+    }
+
+    pub(crate) fn set_header(&mut self, name: String, value: String) {
+        match self
+            .headers
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&name))
+        {
+            Some(header) => *header = (name, value),
+            None => self.headers.push((name, value)),
+        }
+    }
+
+    /*
+    One CONNECT round trip. A non-2xx response is returned rather than turned into an error, with
+    its body read, together with whether the connection can carry another request.
+    */
+    pub(crate) async fn round<T>(
+        &self,
+        io: &mut T,
+        authorization: Option<&str>,
+    ) -> io::Result<(HttpConnectResponse, bool)>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        if let TargetAddr::Domain(domain, _) = &self.target {
+            check_field(domain)?;
+        }
+        let mut req = format!("CONNECT {} HTTP/1.1\r\n", self.target);
+        for (name, value) in &self.headers {
+            if authorization.is_some() && name.eq_ignore_ascii_case("Proxy-Authorization") {
+                continue;
+            }
+            check_field(name)?;
+            check_field(value)?;
+            req.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if let Some(authorization) = authorization {
+            check_field(authorization)?;
+            req.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        }
+        req.push_str("\r\n");
+        write_all(io, req.as_bytes()).await?;
+
+        let mut res = read_response_head(io).await?;
+        if res.is_success() {
+            return Ok((res, true));
+        }
+
+        // Without a single Content-Length the end of the body is unknown, the connection can't be reused.
+        let len = match res.headers("Content-Length").collect::<Vec<_>>()[..] {
+            [len] if res.header("Transfer-Encoding").is_none() => len.trim().parse::<usize>().ok(),
+            _ => None,
+        };
+        if let Some(len) = len {
+            let mut body = vec![0; len.min(MAX_BODY_SIZE)];
+            read_exact(io, &mut body).await?;
+            res.body = body;
+        }
+
+        let close = res
+            .header("Connection")
+            .or_else(|| res.header("Proxy-Connection"))
+            .map(|v| v.trim().eq_ignore_ascii_case("close"));
+        let reusable = match len {
+            Some(len) if len <= MAX_BODY_SIZE => match close {
+                Some(close) => !close,
+                None => res.version != "HTTP/1.0",
+            },
+            _ => false,
+        };
+        Ok((res, reusable))
+    }
+}
+
+/*
+Reads one byte at a time, so that nothing past the header terminator is consumed, after a 2xx it
+belongs to the tunnel.
+*/
+async fn read_head<T>(io: &mut T) -> io::Result<Vec<u8>>
+where
+    T: AsyncIo + ?Sized,
+{
+    let mut head = Vec::with_capacity(256);
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEADER_SIZE {
+            return Err(HttpConnectError::HeaderTooLarge.into());
+        }
+        read_exact(io, &mut byte).await?;
+        head.push(byte[0]);
+    }
+    Ok(head)
+}
+
+fn parse_headers<'a>(lines: impl Iterator<Item = &'a str>) -> Option<Vec<(String, String)>> {
+    let mut headers = vec![];
+    for line in lines.filter(|line| !line.is_empty()) {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or_default().trim();
+        let value = parts.next()?.trim();
+        headers.push((name.to_owned(), value.to_owned()));
+    }
+    Some(headers)
+}
+
+pub(crate) async fn read_response_head<T>(io: &mut T) -> io::Result<HttpConnectResponse>
+where
+    T: AsyncIo + ?Sized,
+{
+    let head = read_head(io).await?;
+    let head = String::from_utf8(head).map_err(|_| HttpConnectError::InvalidResponse)?;
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().ok_or(HttpConnectError::InvalidResponse)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let version = match parts.next() {
+        Some(version) if version.starts_with("HTTP/1.") => version.to_owned(),
+        _ => return Err(HttpConnectError::InvalidResponse.into()),
+    };
+    let status = parts
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(HttpConnectError::InvalidResponse)?;
+    let reason = parts.next().unwrap_or_default().to_owned();
+    let headers = parse_headers(lines).ok_or(HttpConnectError::InvalidResponse)?;
+
+    Ok(HttpConnectResponse {
+        version,
+        status,
+        reason,
+        headers,
+        body: vec![],
+    })
+}
+
+pub(crate) async fn read_request_head<T>(io: &mut T) -> io::Result<HttpConnectRequest>
+where
+    T: AsyncIo + ?Sized,
+{
+    let head = read_head(io).await?;
+    let head = String::from_utf8(head).map_err(|_| HttpConnectError::InvalidRequest)?;
+    let mut lines = head.split("\r\n");
+
+    let request_line = lines.next().ok_or(HttpConnectError::InvalidRequest)?;
+    let mut parts = request_line.split(' ');
+    let (method, authority, version) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(authority), Some(version), None)
+                if !method.is_empty()
+                    && !authority.is_empty()
+                    && version.starts_with("HTTP/1.") =>
+            {
+                (method, authority, version)
+            }
+            _ => return Err(HttpConnectError::InvalidRequest.into()),
+        };
+    let headers = parse_headers(lines).ok_or(HttpConnectError::InvalidRequest)?;
+
+    Ok(HttpConnectRequest {
+        method: method.to_owned(),
+        authority: authority.to_owned(),
+        version: version.to_owned(),
+        headers,
+    })
+}
+
+pub(crate) async fn write_response_head<T>(io: &mut T, res: &HttpConnectResponse) -> io::Result<()>
+where
+    T: AsyncIo + ?Sized,
+{
+    check_field(&res.version)?;
+    check_field(&res.reason)?;
+    let mut head = format!("{} {} {}\r\n", res.version, res.status, res.reason);
+    for (name, value) in &res.headers {
+        check_field(name)?;
+        check_field(value)?;
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !res.is_success() {
+        if res.header("Content-Length").is_none() {
+            head.push_str(&format!("Content-Length: {}\r\n", res.body.len()));
+        }
+        if res.header("Connection").is_none() {
+            head.push_str("Connection: close\r\n");
+        }
+    }
+    head.push_str("\r\n");
+
+    let mut buf = head.into_bytes();
+    buf.extend_from_slice(&res.body);
+    write_all(io, &buf).await
+}
+
+const MAX_ROUNDS: usize = 4;
+
+macro_rules! http_connect_tunnel_grader {
+    () => {
+        use std::fmt;
+        use std::io;
+
+        use async_trait::async_trait;
+
+        use super::{HttpConnectClient, HttpConnectError, HttpConnectResponse, MAX_ROUNDS};
+        use crate::gradable::Downgrader;
+        use crate::http_tunnel::HttpTunnelClientGrader;
+        use crate::proxy_auth::{ProxyAuthenticator, ProxyConnector};
+        use crate::socks5::TargetAddr;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        pub struct HttpConnectTunnelGrader<C = ()> {
+            client: HttpConnectClient,
+            response: Option<HttpConnectResponse>,
+            authenticator: Option<Box<dyn ProxyAuthenticator>>,
+            connector: C,
+        }
+
+        impl<C> fmt::Debug for HttpConnectTunnelGrader<C> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("HttpConnectTunnelGrader")
+                    .field("client", &self.client)
+                    .field("response", &self.response)
+                    .finish()
+            }
+        }
+
+        impl HttpConnectTunnelGrader {
+            pub fn new(target: impl Into<TargetAddr>) -> Self {
+                Self {
+                    client: HttpConnectClient::new(target.into()),
+                    response: None,
+                    authenticator: None,
+                    connector: (),
+                }
+            }
+        }
+
+        impl<C> HttpConnectTunnelGrader<C> {
+            // Replaces the header with the same name, including the default Host. CR, LF or NUL in
+            // the name or value fails the upgrade with InvalidInput.
+            pub fn with_header(
+                mut self,
+                name: impl Into<String>,
+                value: impl Into<String>,
+            ) -> Self {
+                self.client.set_header(name.into(), value.into());
+                self
+            }
+
+            pub fn with_user_agent(self, user_agent: impl Into<String>) -> Self {
+                self.with_header("User-Agent", user_agent)
+            }
+
+            pub fn with_proxy_authorization(self, value: impl Into<String>) -> Self {
+                self.with_header("Proxy-Authorization", value)
+            }
+
+            // Consulted on every 407, its answer replaces any Proxy-Authorization header.
+            pub fn with_authenticator(
+                mut self,
+                authenticator: impl ProxyAuthenticator + 'static,
+            ) -> Self {
+                self.authenticator = Some(Box::new(authenticator));
+                self
+            }
+
+            // Used to retry on a new connection when the proxy closes the one that got the 407.
+            pub fn with_connector<C2>(self, connector: C2) -> HttpConnectTunnelGrader<C2> {
+                HttpConnectTunnelGrader {
+                    client: self.client,
+                    response: self.response,
+                    authenticator: self.authenticator,
+                    connector,
+                }
+            }
+
+            pub fn target(&self) -> &TargetAddr {
+                &self.client.target
+            }
+
+            pub fn response(&self) -> Option<&HttpConnectResponse> {
+                self.response.as_ref()
+            }
+        }
+
+        #[async_trait]
+        impl<S, C> Upgrader<S> for HttpConnectTunnelGrader<C>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            C: ProxyConnector<S> + Send,
+        {
+            type Output = S;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                let uri = self.client.target.to_string();
+                let mut authorization = None;
+                let mut rounds = 0;
+                if let Some(authenticator) = &mut self.authenticator {
+                    authenticator.reset();
+                }
+                loop {
+                    let (response, reusable) = self
+                        .client
+                        .round(&mut Compat(&mut stream), authorization.as_deref())
+                        .await?;
+                    if response.is_success() {
+                        self.response = Some(response);
+                        return Ok(stream);
+                    }
+
+                    rounds += 1;
+                    authorization = match &mut self.authenticator {
+                        Some(authenticator) if response.status == 407 && rounds < MAX_ROUNDS => {
+                            authenticator.authorize("CONNECT", &uri, &response)?
+                        }
+                        _ => None,
+                    };
+                    if authorization.is_none() {
+                        return Err(HttpConnectError::Status(response).into());
+                    }
+
+                    if !reusable {
+                        stream = match self.connector.connect().await? {
+                            Some(stream) => stream,
+                            None => return Err(HttpConnectError::Status(response).into()),
+                        };
+                    }
+                }
+            }
+        }
+
+        // Once CONNECT succeeded the stream is the tunnel, nothing to undo.
+        #[async_trait]
+        impl<S, C> Downgrader<S> for HttpConnectTunnelGrader<C>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            C: ProxyConnector<S> + Send,
+        {
+            async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+
+        impl<S, C> HttpTunnelClientGrader<S> for HttpConnectTunnelGrader<C>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            C: ProxyConnector<S> + Send,
+        {
+        }
+
+        impl<S, C> UpgraderExtRefer<S> for HttpConnectTunnelGrader<C>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            C: ProxyConnector<S> + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output
+            }
+        }
+
+        impl<S, C> UpgraderExtIntoStream<S> for HttpConnectTunnelGrader<C>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            C: ProxyConnector<S> + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+    };
+}
+
+macro_rules! http_connect_server_grader {
+    () => {
+        use super::{read_request_head, write_response_head, HttpConnectRequest};
+        use crate::http_tunnel::HttpTunnelServerGrader;
+
+        /*
+        Answers a CONNECT read from the inbound stream. The filter sees the request and either lets
+        it through, answered with 200, or returns the response to deny it with. After a denial the
+        connection should be closed.
+        */
+        pub struct HttpConnectServerGrader<F> {
+            filter: F,
+            request: Option<HttpConnectRequest>,
+        }
+
+        impl<F> fmt::Debug for HttpConnectServerGrader<F> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct("HttpConnectServerGrader")
+                    .field("request", &self.request)
+                    .finish()
+            }
+        }
+
+        impl<F> HttpConnectServerGrader<F>
+        where
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+            pub fn new(filter: F) -> Self {
+                Self {
+                    filter,
+                    request: None,
+                }
+            }
+
+            pub fn request(&self) -> Option<&HttpConnectRequest> {
+                self.request.as_ref()
+            }
+        }
+
+        #[async_trait]
+        impl<S, F> Upgrader<S> for HttpConnectServerGrader<F>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+            type Output = S;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                let mut io = Compat(&mut stream);
+
+                let request = match read_request_head(&mut io).await {
+                    Ok(request) => request,
+                    Err(err) => {
+                        if err.kind() == io::ErrorKind::InvalidData {
+                            let res = HttpConnectResponse::new(400, "Bad Request");
+                            let _ = write_response_head(&mut io, &res).await;
+                        }
+                        return Err(err);
+                    }
+                };
+
+                let ret = if !request.method.eq_ignore_ascii_case("CONNECT") {
+                    Err(HttpConnectResponse::new(405, "Method Not Allowed")
+                        .with_header("Allow", "CONNECT"))
+                } else if request.target().is_none() {
+                    Err(HttpConnectResponse::new(400, "Bad Request"))
+                } else {
+                    (self.filter)(&request)
+                };
+                self.request = Some(request);
+
+                match ret {
+                    Ok(()) => {
+                        let res = HttpConnectResponse::new(200, "Connection Established");
+                        write_response_head(&mut io, &res).await?;
+                        Ok(stream)
+                    }
+                    Err(res) => {
+                        write_response_head(&mut io, &res).await?;
+                        Err(HttpConnectError::Denied(res).into())
+                    }
+                }
+            }
+        }
+
+        #[async_trait]
+        impl<S, F> Downgrader<S> for HttpConnectServerGrader<F>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+            async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+
+        impl<S, F> HttpTunnelServerGrader<S> for HttpConnectServerGrader<F>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+        }
+
+        impl<S, F> UpgraderExtRefer<S> for HttpConnectServerGrader<F>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output
+            }
+        }
+
+        impl<S, F> UpgraderExtIntoStream<S> for HttpConnectServerGrader<F>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            F: FnMut(&HttpConnectRequest) -> Result<(), HttpConnectResponse> + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    http_connect_tunnel_grader!();
+    http_connect_server_grader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    http_connect_tunnel_grader!();
+    http_connect_server_grader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    http_connect_tunnel_grader!();
+    http_connect_server_grader!();
+}
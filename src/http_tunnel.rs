@@ -1,8 +1,10 @@
-use futures_x_io::{AsyncRead, AsyncWrite};
-
 use crate::gradable::Downgrader;
 use crate::upgradable::Upgrader;
 
 pub trait HttpTunnelClientGrader<S>: Upgrader<S> + Downgrader<S> {}
 
-impl<S> HttpTunnelClientGrader<S> for () where S: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+impl<S> HttpTunnelClientGrader<S> for () where S: Send + 'static {}
+
+pub trait HttpTunnelServerGrader<S>: Upgrader<S> + Downgrader<S> {}
+
+impl<S> HttpTunnelServerGrader<S> for () where S: Send + 'static {}
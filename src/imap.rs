@@ -1,7 +1,11 @@
-use futures_x_io::{AsyncRead, AsyncWrite};
+use std::error;
+use std::fmt;
+use std::io;
+use std::str;
 
+use crate::io_util::{read_exact, read_line, try_read, write_all, AsyncIo};
 use crate::tls::TlsClientUpgrader;
-use crate::upgradable::{UpgradableAsyncStream, Upgrader};
+use crate::upgradable::UpgradableAsyncStream;
 
 /*
 IMAP
@@ -25,11 +29,482 @@ pub type ImapClientInnerStream<S, SU> = UpgradableAsyncStream<S, SU>;
 
 impl<S, SU> ImapClientInnerStream<S, SU>
 where
-    S: AsyncRead + AsyncWrite + Unpin,
-    SU: TlsClientUpgrader<S> + Unpin,
-    <SU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+    SU: TlsClientUpgrader<S>,
 {
     pub fn with_imap_client(stream: S, tls_upgrader: SU) -> Self {
         Self::new(stream, tls_upgrader)
     }
 }
+
+//
+//
+//
+const MAX_LINE_SIZE: usize = 8 * 1024;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ImapError {
+    UnexpectedGreeting(String),
+    StartTlsNotSupported,
+    CommandFailed(String),
+    InvalidResponse,
+    DataAfterStartTls,
+}
+
+impl fmt::Display for ImapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedGreeting(line) => write!(f, "unexpected greeting: {}", line),
+            Self::StartTlsNotSupported => write!(f, "STARTTLS not supported"),
+            Self::CommandFailed(line) => write!(f, "command failed: {}", line),
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::DataAfterStartTls => write!(f, "data after STARTTLS response"),
+        }
+    }
+}
+
+impl error::Error for ImapError {}
+
+impl From<ImapError> for io::Error {
+    fn from(err: ImapError) -> Self {
+        let kind = match err {
+            ImapError::InvalidResponse | ImapError::DataAfterStartTls => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+// A line ending with {n} or {n+} is followed by n bytes of literal and the rest of the response.
+fn literal_len(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|b| *b == b'{')?;
+    let digits = &line[start + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    str::from_utf8(digits).ok()?.parse().ok()
+}
+
+async fn read_response<T>(io: &mut T) -> io::Result<String>
+where
+    T: AsyncIo + ?Sized,
+{
+    let mut response = vec![];
+    loop {
+        let line = read_line(io, MAX_LINE_SIZE).await?;
+        response.extend_from_slice(&line);
+        match literal_len(&line) {
+            Some(n) if response.len() + n <= MAX_LINE_SIZE => {
+                let mut literal = vec![0; n];
+                read_exact(io, &mut literal).await?;
+                response.extend_from_slice(b"\r\n");
+                response.extend_from_slice(&literal);
+            }
+            Some(_) => return Err(ImapError::InvalidResponse.into()),
+            None => {
+                return String::from_utf8(response).map_err(|_| ImapError::InvalidResponse.into())
+            }
+        }
+    }
+}
+
+fn has_starttls(capability: &str) -> bool {
+    capability
+        .split(' ')
+        .any(|cap| cap.eq_ignore_ascii_case("STARTTLS"))
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImapStartTlsClient {}
+
+impl ImapStartTlsClient {
+    // Returns the untagged responses once the tagged one is OK.
+    async fn command<T>(&self, io: &mut T, tag: &str, command: &str) -> io::Result<Vec<String>>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        write_all(io, format!("{} {}\r\n", tag, command).as_bytes()).await?;
+
+        let mut untagged = vec![];
+        loop {
+            let response = read_response(io).await?;
+            if let Some(untagged_response) = response.strip_prefix("* ") {
+                untagged.push(untagged_response.to_owned());
+                continue;
+            }
+
+            let status = response
+                .strip_prefix(tag)
+                .and_then(|rest| rest.strip_prefix(' '))
+                .and_then(|rest| rest.split(' ').next())
+                .ok_or(ImapError::InvalidResponse)?;
+            return match status.to_ascii_uppercase().as_str() {
+                "OK" => Ok(untagged),
+                "NO" | "BAD" => Err(ImapError::CommandFailed(response).into()),
+                _ => Err(ImapError::InvalidResponse.into()),
+            };
+        }
+    }
+
+    // Everything up to the TLS handshake.
+    pub(crate) async fn start_tls<T>(&self, io: &mut T) -> io::Result<()>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        let greeting = read_response(io).await?;
+        let text = match greeting.strip_prefix("* ") {
+            Some(rest) if rest.len() >= 3 && rest[..3].eq_ignore_ascii_case("OK ") => &rest[3..],
+            // PREAUTH too, a session that is already authenticated can't be protected anymore.
+            _ => return Err(ImapError::UnexpectedGreeting(greeting).into()),
+        };
+
+        let supported = match text
+            .strip_prefix("[CAPABILITY ")
+            .and_then(|rest| rest.split(']').next())
+        {
+            Some(capability) => has_starttls(capability),
+            None => self
+                .command(io, "a1", "CAPABILITY")
+                .await?
+                .iter()
+                .filter_map(|untagged| untagged.strip_prefix("CAPABILITY "))
+                .any(has_starttls),
+        };
+        if !supported {
+            return Err(ImapError::StartTlsNotSupported.into());
+        }
+
+        // Untagged responses up to here are dropped, they came in plaintext.
+        self.command(io, "a2", "STARTTLS").await?;
+
+        // The server must wait for the handshake, anything already sent could be injected.
+        let mut byte = [0; 1];
+        if matches!(try_read(io, &mut byte).await?, Some(n) if n > 0) {
+            return Err(ImapError::DataAfterStartTls.into());
+        }
+        Ok(())
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone)]
+pub(crate) struct ImapStartTlsServer {
+    pub(crate) greeting: String,
+    pub(crate) capabilities: Vec<String>,
+}
+
+impl Default for ImapStartTlsServer {
+    fn default() -> Self {
+        Self {
+            greeting: "IMAP4rev1 Service Ready".to_owned(),
+            capabilities: vec![],
+        }
+    }
+}
+
+impl ImapStartTlsServer {
+    fn capability(&self) -> String {
+        let mut capability = "IMAP4rev1 STARTTLS LOGINDISABLED".to_owned();
+        for cap in &self.capabilities {
+            capability.push(' ');
+            capability.push_str(cap);
+        }
+        capability
+    }
+
+    /*
+    Greets and answers commands until a STARTTLS. Everything but CAPABILITY, NOOP and LOGOUT is
+    refused, credentials never travel in plaintext. Returns once the client was told to start
+    the TLS handshake.
+    */
+    pub(crate) async fn handshake<T>(&self, io: &mut T) -> io::Result<()>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        let greeting = format!(
+            "* OK [CAPABILITY {}] {}\r\n",
+            self.capability(),
+            self.greeting
+        );
+        write_all(io, greeting.as_bytes()).await?;
+
+        loop {
+            let line = read_line(io, MAX_LINE_SIZE).await?;
+            let line = String::from_utf8_lossy(&line);
+            let mut parts = line.splitn(3, ' ');
+            let (tag, command) = match (parts.next(), parts.next()) {
+                (Some(tag), Some(command)) if !tag.is_empty() => (tag, command),
+                _ => {
+                    write_all(io, b"* BAD Invalid command\r\n").await?;
+                    continue;
+                }
+            };
+
+            let reply = match command.to_ascii_uppercase().as_str() {
+                "CAPABILITY" => format!(
+                    "* CAPABILITY {}\r\n{} OK CAPABILITY completed\r\n",
+                    self.capability(),
+                    tag
+                ),
+                "NOOP" => format!("{} OK NOOP completed\r\n", tag),
+                "LOGOUT" => {
+                    let reply = format!("* BYE Logging out\r\n{} OK LOGOUT completed\r\n", tag);
+                    write_all(io, reply.as_bytes()).await?;
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "logged out before STARTTLS",
+                    ));
+                }
+                "STARTTLS" if parts.next().is_some() => {
+                    format!("{} BAD STARTTLS takes no arguments\r\n", tag)
+                }
+                "STARTTLS" => {
+                    // Anything sent after STARTTLS, before our reply, was never meant to be
+                    // protected by TLS, ref https://tools.ietf.org/html/rfc3501#section-6.2.1
+                    let mut byte = [0; 1];
+                    if matches!(try_read(io, &mut byte).await?, Some(n) if n > 0) {
+                        let reply = format!("{} BAD STARTTLS must not be pipelined\r\n", tag);
+                        write_all(io, reply.as_bytes()).await?;
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "command pipelined after STARTTLS",
+                        ));
+                    }
+
+                    let reply = format!("{} OK Begin TLS negotiation now\r\n", tag);
+                    write_all(io, reply.as_bytes()).await?;
+                    return Ok(());
+                }
+                _ => format!("{} NO [PRIVACYREQUIRED] Run STARTTLS first\r\n", tag),
+            };
+            write_all(io, reply.as_bytes()).await?;
+        }
+    }
+}
+
+//
+//
+//
+macro_rules! imap_start_tls_server_upgrader {
+    () => {
+        use std::io;
+
+        use async_trait::async_trait;
+
+        use super::ImapStartTlsServer;
+        use crate::tls::TlsServerUpgrader;
+        use crate::upgradable::{UpgradableAsyncStream, Upgrader};
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        /*
+        The Case1 flow for servers, TLS is run once the client asked for it with STARTTLS.
+        */
+        #[derive(Debug, Clone)]
+        pub struct ImapStartTlsServerUpgrader<TU> {
+            server: ImapStartTlsServer,
+            tls_upgrader: TU,
+        }
+
+        impl<TU> ImapStartTlsServerUpgrader<TU> {
+            pub fn new(tls_upgrader: TU) -> Self {
+                Self {
+                    server: Default::default(),
+                    tls_upgrader,
+                }
+            }
+
+            // The text after the greeting's CAPABILITY response code.
+            pub fn with_greeting(mut self, greeting: impl Into<String>) -> Self {
+                self.server.greeting = greeting.into();
+                self
+            }
+
+            // Advertised along with IMAP4rev1 STARTTLS LOGINDISABLED.
+            pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+                self.server.capabilities.push(capability.into());
+                self
+            }
+
+            pub fn get_ref(&self) -> &TU {
+                &self.tls_upgrader
+            }
+
+            pub fn get_mut(&mut self) -> &mut TU {
+                &mut self.tls_upgrader
+            }
+
+            pub fn into_inner(self) -> TU {
+                self.tls_upgrader
+            }
+        }
+
+        #[async_trait]
+        impl<S, TU> Upgrader<S> for ImapStartTlsServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<S> + Send,
+        {
+            type Output = TU::Output;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                self.server.handshake(&mut Compat(&mut stream)).await?;
+                self.tls_upgrader.upgrade(stream).await
+            }
+        }
+
+        impl<S, TU> TlsServerUpgrader<S> for ImapStartTlsServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<S> + Send,
+        {
+        }
+
+        impl<S, TU> UpgraderExtRefer<S> for ImapStartTlsServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<S> + UpgraderExtRefer<S> + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                TU::get_ref(output)
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                TU::get_mut(output)
+            }
+        }
+
+        impl<S, TU> UpgraderExtIntoStream<S> for ImapStartTlsServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<S> + UpgraderExtIntoStream<S> + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                TU::into_stream(output)
+            }
+        }
+
+        //
+        pub type ImapServerInnerStream<S, TU> =
+            UpgradableAsyncStream<S, ImapStartTlsServerUpgrader<TU>>;
+
+        impl<S, TU> ImapServerInnerStream<S, TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<S> + Send,
+        {
+            pub fn with_imap_server(stream: S, tls_upgrader: TU) -> Self {
+                Self::new(stream, ImapStartTlsServerUpgrader::new(tls_upgrader))
+            }
+        }
+    };
+}
+
+macro_rules! imap_start_tls_upgrader {
+    () => {
+        use super::ImapStartTlsClient;
+        use crate::tls::TlsClientUpgrader;
+
+        /*
+        The Case1 flow for clients, the stream is ready for LOGIN afterwards. Capabilities should
+        be asked for again, the ones sent before TLS can't be trusted.
+        */
+        #[derive(Debug, Clone)]
+        pub struct ImapStartTlsUpgrader<TU> {
+            client: ImapStartTlsClient,
+            tls_upgrader: TU,
+        }
+
+        impl<TU> ImapStartTlsUpgrader<TU> {
+            pub fn new(tls_upgrader: TU) -> Self {
+                Self {
+                    client: Default::default(),
+                    tls_upgrader,
+                }
+            }
+
+            pub fn get_ref(&self) -> &TU {
+                &self.tls_upgrader
+            }
+
+            pub fn get_mut(&mut self) -> &mut TU {
+                &mut self.tls_upgrader
+            }
+
+            pub fn into_inner(self) -> TU {
+                self.tls_upgrader
+            }
+        }
+
+        #[async_trait]
+        impl<S, TU> Upgrader<S> for ImapStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + Send,
+        {
+            type Output = TU::Output;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                self.client.start_tls(&mut Compat(&mut stream)).await?;
+                self.tls_upgrader.upgrade(stream).await
+            }
+        }
+
+        impl<S, TU> TlsClientUpgrader<S> for ImapStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + Send,
+        {
+        }
+
+        impl<S, TU> UpgraderExtRefer<S> for ImapStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + UpgraderExtRefer<S> + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                TU::get_ref(output)
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                TU::get_mut(output)
+            }
+        }
+
+        impl<S, TU> UpgraderExtIntoStream<S> for ImapStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + UpgraderExtIntoStream<S> + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                TU::into_stream(output)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    imap_start_tls_server_upgrader!();
+    imap_start_tls_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    imap_start_tls_server_upgrader!();
+    imap_start_tls_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    imap_start_tls_server_upgrader!();
+    imap_start_tls_upgrader!();
+}
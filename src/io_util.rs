@@ -0,0 +1,199 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/*
+Handshakes (socks, http connect, ...) are written once against AsyncIo, every io flavor wraps its
+stream in its own adapter, so the protocol code doesn't depend on which flavors are enabled.
+*/
+pub(crate) trait AsyncIo {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>>;
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<()>>;
+    // poll_close or poll_shutdown
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<io::Result<()>>;
+}
+
+pub(crate) async fn read<T>(io: &mut T, buf: &mut [u8]) -> io::Result<usize>
+where
+    T: AsyncIo + ?Sized,
+{
+    PollFn(|cx: &mut Context| io.poll_read(cx, buf)).await
+}
+
+pub(crate) async fn read_exact<T>(io: &mut T, mut buf: &mut [u8]) -> io::Result<()>
+where
+    T: AsyncIo + ?Sized,
+{
+    while !buf.is_empty() {
+        let n = read(io, buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut buf[n..];
+    }
+    Ok(())
+}
+
+pub(crate) async fn write_all<T>(io: &mut T, mut buf: &[u8]) -> io::Result<()>
+where
+    T: AsyncIo + ?Sized,
+{
+    while !buf.is_empty() {
+        let n = PollFn(|cx: &mut Context| io.poll_write(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+    }
+    PollFn(|cx: &mut Context| io.poll_flush(cx)).await
+}
+
+/*
+Reads one byte at a time up to and including LF, so nothing after the line is taken from the
+stream. The line is returned without CRLF.
+*/
+pub(crate) async fn read_line<T>(io: &mut T, max: usize) -> io::Result<Vec<u8>>
+where
+    T: AsyncIo + ?Sized,
+{
+    let mut line = vec![];
+    let mut byte = [0; 1];
+    loop {
+        read_exact(io, &mut byte).await?;
+        if byte[0] == b'\n' {
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+            return Ok(line);
+        }
+        if line.len() >= max {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+        }
+        line.push(byte[0]);
+    }
+}
+
+// Reads only what has already arrived, None if reading would have to wait.
+pub(crate) async fn try_read<T>(io: &mut T, buf: &mut [u8]) -> io::Result<Option<usize>>
+where
+    T: AsyncIo + ?Sized,
+{
+    PollFn(|cx: &mut Context| match io.poll_read(cx, buf) {
+        Poll::Ready(ret) => Poll::Ready(ret.map(Some)),
+        Poll::Pending => Poll::Ready(Ok(None)),
+    })
+    .await
+}
+
+pub(crate) struct PollFn<F>(pub(crate) F);
+
+impl<F, T> Future for PollFn<F>
+where
+    F: FnMut(&mut Context) -> Poll<T> + Unpin,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        (self.get_mut().0)(cx)
+    }
+}
+
+#[cfg(feature = "futures_io")]
+pub(crate) mod futures_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use super::AsyncIo;
+
+    pub(crate) struct Compat<'a, S>(pub(crate) &'a mut S);
+
+    impl<'a, S> AsyncIo for Compat<'a, S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.0).poll_read(cx, buf)
+        }
+        fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.0).poll_write(cx, buf)
+        }
+        fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_flush(cx)
+        }
+        fn poll_close(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_close(cx)
+        }
+    }
+}
+
+#[cfg(feature = "tokio_io")]
+pub(crate) mod tokio_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use super::AsyncIo;
+
+    pub(crate) struct Compat<'a, S>(pub(crate) &'a mut S);
+
+    impl<'a, S> AsyncIo for Compat<'a, S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.0).poll_read(cx, buf)
+        }
+        fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.0).poll_write(cx, buf)
+        }
+        fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_flush(cx)
+        }
+        fn poll_close(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(feature = "tokio1_io")]
+pub(crate) mod tokio1_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio1::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use super::AsyncIo;
+
+    pub(crate) struct Compat<'a, S>(pub(crate) &'a mut S);
+
+    impl<'a, S> AsyncIo for Compat<'a, S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let mut buf = ReadBuf::new(buf);
+            match Pin::new(&mut *self.0).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                Poll::Pending => Poll::Pending,
+            }
+        }
+        fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut *self.0).poll_write(cx, buf)
+        }
+        fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_flush(cx)
+        }
+        fn poll_close(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut *self.0).poll_shutdown(cx)
+        }
+    }
+}
@@ -1,83 +1,174 @@
 cfg_if::cfg_if! {
-    if #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))] {
+    if #[cfg(any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io"))] {
         pub mod syncable_with_context;
-        pub use syncable_with_context::SyncableWithContextAsyncStream;
-    } else if #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))] {
-        pub mod syncable_with_context;
-        pub use syncable_with_context::SyncableWithContextAsyncStream;
     }
 }
 
 cfg_if::cfg_if! {
-    if #[cfg(all(feature = "syncable_with_waker", feature = "futures_io", not(feature = "tokio_io")))] {
-        pub mod syncable_with_waker;
-        pub use syncable_with_waker::SyncableWithWakerAsyncStream;
-    } else if #[cfg(all(feature = "syncable_with_waker", not(feature = "futures_io"), feature = "tokio_io"))] {
+    if #[cfg(all(feature = "futures_io", not(feature = "tokio_io"), not(feature = "tokio1_io")))] {
+        pub use syncable_with_context::futures_io::SyncableWithContextAsyncStream;
+    } else if #[cfg(all(not(feature = "futures_io"), feature = "tokio_io", not(feature = "tokio1_io")))] {
+        pub use syncable_with_context::tokio_io::SyncableWithContextAsyncStream;
+    } else if #[cfg(all(not(feature = "futures_io"), not(feature = "tokio_io"), feature = "tokio1_io"))] {
+        pub use syncable_with_context::tokio1_io::SyncableWithContextAsyncStream;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "syncable_with_waker", any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io")))] {
         pub mod syncable_with_waker;
-        pub use syncable_with_waker::SyncableWithWakerAsyncStream;
     }
 }
 
 cfg_if::cfg_if! {
-    if #[cfg(all(feature = "unionable", feature = "futures_io", not(feature = "tokio_io")))] {
-        pub mod unionable;
-        pub use unionable::UnionableAsyncStream;
-    } else if #[cfg(all(feature = "unionable", not(feature = "futures_io"), feature = "tokio_io"))] {
+    if #[cfg(all(feature = "syncable_with_waker", feature = "futures_io", not(feature = "tokio_io"), not(feature = "tokio1_io")))] {
+        pub use syncable_with_waker::futures_io::SyncableWithWakerAsyncStream;
+    } else if #[cfg(all(feature = "syncable_with_waker", not(feature = "futures_io"), feature = "tokio_io", not(feature = "tokio1_io")))] {
+        pub use syncable_with_waker::tokio_io::SyncableWithWakerAsyncStream;
+    } else if #[cfg(all(feature = "syncable_with_waker", not(feature = "futures_io"), not(feature = "tokio_io"), feature = "tokio1_io"))] {
+        pub use syncable_with_waker::tokio1_io::SyncableWithWakerAsyncStream;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "unionable", any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io")))] {
         pub mod unionable;
         pub use unionable::UnionableAsyncStream;
     }
 }
 
 cfg_if::cfg_if! {
-    if #[cfg(all(feature = "upgradable", feature = "futures_io", not(feature = "tokio_io")))] {
+    if #[cfg(all(feature = "upgradable", any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io")))] {
+        #[macro_use]
         pub mod upgradable;
         pub use upgradable::{UpgradableAsyncStream, Upgrader};
 
+        pub mod upgradable_error;
+        pub use upgradable_error::UpgradeError;
+
         pub mod upgradable_ext;
         pub use upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
 
         pub mod gradable;
         pub use gradable::{Downgrader, GradableAsyncStream};
 
+        pub mod chained;
+        pub use chained::ChainedUpgrader;
+
+        pub mod poll_upgradable;
+        pub use poll_upgradable::{AsyncTraitAdapter, PassThroughPollUpgrader, PollDowngrader, PollUpgradableAsyncStream, PollUpgrader};
+
+        pub mod boxed;
+
+        pub mod timeout;
+        pub use timeout::{TimeoutUpgrader, Timer};
+
         //
+        mod io_util;
+
+        pub mod rewind;
+
         pub mod tls;
         pub use tls::{TlsClientUpgrader, TlsServerUpgrader};
 
         pub mod http_tunnel;
-        pub use http_tunnel::HttpTunnelClientGrader;
+        pub use http_tunnel::{HttpTunnelClientGrader, HttpTunnelServerGrader};
 
-        pub mod http;
-        pub use http::{HttpClientInnerStream, HttpClientProxy};
+        pub mod http_connect;
+        pub use http_connect::{HttpConnectError, HttpConnectRequest, HttpConnectResponse};
 
-        pub mod imap;
-        pub use imap::ImapClientInnerStream;
+        pub mod proxy_auth;
+        pub use proxy_auth::{ProxyAuthenticator, ProxyConnector};
 
-        pub mod smtp;
-        pub use smtp::SmtpClientInnerStream;
-    } else if #[cfg(all(feature = "upgradable", not(feature = "futures_io"), feature = "tokio_io"))] {
-        pub mod upgradable;
-        pub use upgradable::{UpgradableAsyncStream, Upgrader};
+        pub mod socks5;
+        pub use socks5::{Socks5Error, Socks5ProxyUpgrader, TargetAddr};
 
-        pub mod upgradable_ext;
-        pub use upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+        pub mod socks4;
+        pub use socks4::{Socks4Error, Socks4ProxyUpgrader};
 
-        pub mod gradable;
-        pub use gradable::{Downgrader, GradableAsyncStream};
+        pub mod proxy_protocol;
+        pub use proxy_protocol::{ProxyProtocolError, ProxyProtocolHeader, ProxyProtocolTlv, ProxyProtocolVersion};
 
-        //
-        pub mod tls;
-        pub use tls::{TlsClientUpgrader, TlsServerUpgrader};
+        pub mod proxy_resolver;
+        pub use proxy_resolver::{NoProxy, ProxyResolver, ProxyScheme, ProxyUrl};
 
-        pub mod http_tunnel;
-        pub use http_tunnel::HttpTunnelClientGrader;
+        pub mod proxy_chain;
+        pub use proxy_chain::{ProxyChainError, ProxyHopStage};
+
+        pub mod relay;
+        pub use relay::RelayCounts;
 
         pub mod http;
-        pub use http::{HttpClientInnerStream, HttpClientProxy};
+        pub use http::{HttpClientInnerStream, HttpClientProxy, HttpProxyKind, HttpTransport};
 
         pub mod imap;
-        pub use imap::ImapClientInnerStream;
+        pub use imap::{ImapClientInnerStream, ImapError};
 
         pub mod smtp;
-        pub use smtp::SmtpClientInnerStream;
+        pub use smtp::{SmtpClientInnerStream, SmtpError, SmtpReply};
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "upgradable", feature = "proxy_auth", any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io")))] {
+        pub use proxy_auth::{BasicAuth, DigestAuth};
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "upgradable", feature = "futures_io", not(feature = "tokio_io"), not(feature = "tokio1_io")))] {
+        pub use boxed::futures_io::{BoxUpgrader, BoxedAsyncStream};
+        pub use http_connect::futures_io::{HttpConnectServerGrader, HttpConnectTunnelGrader};
+        pub use imap::futures_io::{ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader};
+        pub use proxy_chain::futures_io::ProxyChain;
+        pub use proxy_protocol::futures_io::{ProxyProtocolAsyncStream, ProxyProtocolClientUpgrader, ProxyProtocolServerUpgrader};
+        pub use proxy_resolver::futures_io::{resolve_http_client_proxy, ResolvedHttpClientProxy};
+        pub use relay::futures_io::{relay, relay_with_idle_timeout};
+        pub use rewind::futures_io::{RewindAsyncStream, RewindUpgrader};
+        pub use smtp::futures_io::SmtpStartTlsUpgrader;
+        pub use socks4::futures_io::Socks4ClientUpgrader;
+        pub use socks5::futures_io::Socks5ClientUpgrader;
+    } else if #[cfg(all(feature = "upgradable", not(feature = "futures_io"), feature = "tokio_io", not(feature = "tokio1_io")))] {
+        pub use boxed::tokio_io::{BoxUpgrader, BoxedAsyncStream};
+        pub use http_connect::tokio_io::{HttpConnectServerGrader, HttpConnectTunnelGrader};
+        pub use imap::tokio_io::{ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader};
+        pub use proxy_chain::tokio_io::ProxyChain;
+        pub use proxy_protocol::tokio_io::{ProxyProtocolAsyncStream, ProxyProtocolClientUpgrader, ProxyProtocolServerUpgrader};
+        pub use proxy_resolver::tokio_io::{resolve_http_client_proxy, ResolvedHttpClientProxy};
+        pub use relay::tokio_io::{relay, relay_with_idle_timeout};
+        pub use rewind::tokio_io::{RewindAsyncStream, RewindUpgrader};
+        pub use smtp::tokio_io::SmtpStartTlsUpgrader;
+        pub use socks4::tokio_io::Socks4ClientUpgrader;
+        pub use socks5::tokio_io::Socks5ClientUpgrader;
+    } else if #[cfg(all(feature = "upgradable", not(feature = "futures_io"), not(feature = "tokio_io"), feature = "tokio1_io"))] {
+        pub use boxed::tokio1_io::{BoxUpgrader, BoxedAsyncStream};
+        pub use http_connect::tokio1_io::{HttpConnectServerGrader, HttpConnectTunnelGrader};
+        pub use imap::tokio1_io::{ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader};
+        pub use proxy_chain::tokio1_io::ProxyChain;
+        pub use proxy_protocol::tokio1_io::{ProxyProtocolAsyncStream, ProxyProtocolClientUpgrader, ProxyProtocolServerUpgrader};
+        pub use proxy_resolver::tokio1_io::{resolve_http_client_proxy, ResolvedHttpClientProxy};
+        pub use relay::tokio1_io::{relay, relay_with_idle_timeout};
+        pub use rewind::tokio1_io::{RewindAsyncStream, RewindUpgrader};
+        pub use smtp::tokio1_io::SmtpStartTlsUpgrader;
+        pub use socks4::tokio1_io::Socks4ClientUpgrader;
+        pub use socks5::tokio1_io::Socks5ClientUpgrader;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "upgradable", feature = "unionable", any(feature = "futures_io", feature = "tokio_io", feature = "tokio1_io")))] {
+        mod conditional;
+
+        pub mod sniffing;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "upgradable", feature = "unionable", feature = "futures_io", not(feature = "tokio_io"), not(feature = "tokio1_io")))] {
+        pub use sniffing::futures_io::SniffingServerUpgrader;
+    } else if #[cfg(all(feature = "upgradable", feature = "unionable", not(feature = "futures_io"), feature = "tokio_io", not(feature = "tokio1_io")))] {
+        pub use sniffing::tokio_io::SniffingServerUpgrader;
+    } else if #[cfg(all(feature = "upgradable", feature = "unionable", not(feature = "futures_io"), not(feature = "tokio_io"), feature = "tokio1_io"))] {
+        pub use sniffing::tokio1_io::SniffingServerUpgrader;
     }
 }
@@ -0,0 +1,407 @@
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::gradable::Downgrader;
+use crate::upgradable::{State, Transition, Upgrader};
+use crate::upgradable_error::UpgradeError;
+
+/*
+Like AsyncSeek::start_seek and poll_complete in tokio, the upgrader keeps the in-flight upgrade
+itself, so nothing is boxed and neither the stream nor the upgrader has to be Send.
+*/
+pub trait PollUpgrader<S> {
+    type Output;
+    fn start_upgrade(&mut self, stream: S);
+    fn poll_upgrade(&mut self, cx: &mut Context) -> Poll<io::Result<Self::Output>>;
+    fn upgrade_required(&self) -> bool {
+        true
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        None
+    }
+}
+
+pub trait PollDowngrader<S>: PollUpgrader<S> {
+    fn start_downgrade(&mut self, output: Self::Output);
+    fn poll_downgrade(&mut self, cx: &mut Context) -> Poll<io::Result<S>>;
+    fn downgrade_required(&self) -> bool {
+        true
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        None
+    }
+}
+
+//
+// The poll counterpart of the () Upgrader, hands the stream back unchanged. Unlike (), it has
+// somewhere to keep the stream between start and poll.
+//
+pub struct PassThroughPollUpgrader<S> {
+    stream: Option<S>,
+}
+
+impl<S> PassThroughPollUpgrader<S> {
+    pub fn new() -> Self {
+        Self { stream: None }
+    }
+}
+
+impl<S> Default for PassThroughPollUpgrader<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> PollUpgrader<S> for PassThroughPollUpgrader<S> {
+    type Output = S;
+    fn start_upgrade(&mut self, stream: S) {
+        self.stream = Some(stream);
+    }
+    fn poll_upgrade(&mut self, _: &mut Context) -> Poll<io::Result<Self::Output>> {
+        Poll::Ready(
+            self.stream
+                .take()
+                .ok_or_else(|| UpgradeError::NotUpgraded.into()),
+        )
+    }
+    fn upgrade_required(&self) -> bool {
+        false
+    }
+}
+
+impl<S> PollDowngrader<S> for PassThroughPollUpgrader<S> {
+    fn start_downgrade(&mut self, output: Self::Output) {
+        self.stream = Some(output);
+    }
+    fn poll_downgrade(&mut self, _: &mut Context) -> Poll<io::Result<S>> {
+        Poll::Ready(
+            self.stream
+                .take()
+                .ok_or_else(|| UpgradeError::NotUpgraded.into()),
+        )
+    }
+    fn downgrade_required(&self) -> bool {
+        false
+    }
+}
+
+//
+// Adapts an async-trait Upgrader (and Downgrader), the in-flight future is still boxed.
+//
+pub struct AsyncTraitAdapter<S, U>
+where
+    U: Upgrader<S>,
+{
+    state: AdapterState<S, U>,
+    // Set when start_upgrade or start_downgrade is called while busy, returned by the next poll.
+    error: Option<UpgradeError>,
+}
+
+enum AdapterState<S, U>
+where
+    U: Upgrader<S>,
+{
+    Idle(U),
+    Upgrading(Transition<U, U::Output>),
+    Downgrading(Transition<U, S>),
+    Poisoned,
+}
+
+impl<S, U> AsyncTraitAdapter<S, U>
+where
+    U: Upgrader<S>,
+{
+    pub fn new(upgrader: U) -> Self {
+        Self {
+            state: AdapterState::Idle(upgrader),
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> Option<&U> {
+        match &self.state {
+            AdapterState::Idle(upgrader) => Some(upgrader),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut U> {
+        match &mut self.state {
+            AdapterState::Idle(upgrader) => Some(upgrader),
+            _ => None,
+        }
+    }
+
+    pub fn into_inner(self) -> Option<U> {
+        match self.state {
+            AdapterState::Idle(upgrader) => Some(upgrader),
+            _ => None,
+        }
+    }
+
+    fn take_idle(&mut self) -> Option<U> {
+        match mem::replace(&mut self.state, AdapterState::Poisoned) {
+            AdapterState::Idle(upgrader) => Some(upgrader),
+            state => {
+                self.error = Some(match state {
+                    AdapterState::Poisoned => UpgradeError::Poisoned,
+                    _ => UpgradeError::InProgress,
+                });
+                self.state = state;
+                None
+            }
+        }
+    }
+}
+
+impl<S, U> PollUpgrader<S> for AsyncTraitAdapter<S, U>
+where
+    S: Send + 'static,
+    U: Upgrader<S> + Send + 'static,
+{
+    type Output = U::Output;
+    fn start_upgrade(&mut self, stream: S) {
+        let mut upgrader = match self.take_idle() {
+            Some(upgrader) => upgrader,
+            None => return,
+        };
+        self.state = AdapterState::Upgrading(Box::pin(async move {
+            let ret = upgrader.upgrade(stream).await;
+            (upgrader, ret)
+        }));
+    }
+    fn poll_upgrade(&mut self, cx: &mut Context) -> Poll<io::Result<Self::Output>> {
+        if let Some(err) = self.error.take() {
+            return Poll::Ready(Err(err.into()));
+        }
+        match &mut self.state {
+            AdapterState::Upgrading(transition) => match transition.as_mut().poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready((upgrader, ret)) => {
+                    self.state = AdapterState::Idle(upgrader);
+                    Poll::Ready(ret)
+                }
+            },
+            _ => Poll::Ready(Err(UpgradeError::NotUpgraded.into())),
+        }
+    }
+    fn upgrade_required(&self) -> bool {
+        match &self.state {
+            AdapterState::Idle(upgrader) => upgrader.upgrade_required(),
+            _ => false,
+        }
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        match &mut self.state {
+            AdapterState::Idle(upgrader) => upgrader.recover_stream(),
+            _ => None,
+        }
+    }
+}
+
+impl<S, U> PollDowngrader<S> for AsyncTraitAdapter<S, U>
+where
+    S: Send + 'static,
+    U: Downgrader<S> + Send + 'static,
+    U::Output: Send + 'static,
+{
+    fn start_downgrade(&mut self, output: Self::Output) {
+        let mut upgrader = match self.take_idle() {
+            Some(upgrader) => upgrader,
+            None => return,
+        };
+        self.state = AdapterState::Downgrading(Box::pin(async move {
+            let ret = upgrader.downgrade(output).await;
+            (upgrader, ret)
+        }));
+    }
+    fn poll_downgrade(&mut self, cx: &mut Context) -> Poll<io::Result<S>> {
+        if let Some(err) = self.error.take() {
+            return Poll::Ready(Err(err.into()));
+        }
+        match &mut self.state {
+            AdapterState::Downgrading(transition) => match transition.as_mut().poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready((upgrader, ret)) => {
+                    self.state = AdapterState::Idle(upgrader);
+                    Poll::Ready(ret)
+                }
+            },
+            _ => Poll::Ready(Err(UpgradeError::NotUpgraded.into())),
+        }
+    }
+    fn downgrade_required(&self) -> bool {
+        match &self.state {
+            AdapterState::Idle(upgrader) => upgrader.downgrade_required(),
+            _ => false,
+        }
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        match &mut self.state {
+            AdapterState::Idle(upgrader) => upgrader.recover_output(),
+            _ => None,
+        }
+    }
+}
+
+//
+//
+//
+pub struct PollUpgradableAsyncStream<S, SU>
+where
+    SU: PollUpgrader<S>,
+{
+    inner: Inner<S, SU>,
+}
+
+// The upgrader keeps the in-flight upgrade and downgrade, so it's all State holds.
+type Inner<S, SU> = State<S, <SU as PollUpgrader<S>>::Output, SU, SU, SU>;
+
+impl<S, SU> PollUpgradableAsyncStream<S, SU>
+where
+    SU: PollUpgrader<S>,
+{
+    pub fn new(stream: S, upgrader: SU) -> Self {
+        Self {
+            inner: Inner::Pending(stream, upgrader),
+        }
+    }
+
+    pub fn with_upgraded_stream_and_upgrader(stream: SU::Output, upgrader: SU) -> Self {
+        Self {
+            inner: Inner::Upgraded(stream, upgrader),
+        }
+    }
+
+    pub fn is_upgraded(&self) -> bool {
+        matches!(self.inner, Inner::Upgraded(_, _))
+    }
+
+    pub fn is_upgrading(&self) -> bool {
+        matches!(self.inner, Inner::Upgrading(_))
+    }
+
+    pub fn is_downgrading(&self) -> bool {
+        matches!(self.inner, Inner::Downgrading(_))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.inner, Inner::Poisoned)
+    }
+
+    pub fn upgrade_required(&self) -> bool {
+        match &self.inner {
+            Inner::Pending(_, upgrader) => upgrader.upgrade_required(),
+            _ => false,
+        }
+    }
+
+    pub fn try_into_stream(self) -> Result<S, UpgradeError> {
+        match self.inner {
+            Inner::Pending(s, _) => Ok(s),
+            Inner::Upgraded(_, _) => Err(UpgradeError::AlreadyUpgraded),
+            inner => Err(inner.unavailable_error()),
+        }
+    }
+
+    pub fn try_into_upgraded_stream(self) -> Result<SU::Output, UpgradeError> {
+        match self.inner {
+            Inner::Pending(_, _) => Err(UpgradeError::NotUpgraded),
+            Inner::Upgraded(s, _) => Ok(s),
+            inner => Err(inner.unavailable_error()),
+        }
+    }
+
+    pub fn upgrade(&mut self) -> UpgradeFuture<'_, S, SU> {
+        UpgradeFuture { stream: self }
+    }
+
+    pub fn poll_upgrade(&mut self, cx: &mut Context) -> Poll<Result<(), UpgradeError>> {
+        self.inner.poll_upgrade_with(
+            cx,
+            |upgrader| upgrader.upgrade_required(),
+            |mut upgrader, stream| {
+                upgrader.start_upgrade(stream);
+                upgrader
+            },
+            |mut upgrader, cx| match upgrader.poll_upgrade(cx) {
+                Poll::Pending => Err(upgrader),
+                Poll::Ready(ret) => Ok((upgrader, ret)),
+            },
+            |upgrader| upgrader.recover_stream(),
+        )
+    }
+}
+
+impl<S, SU> PollUpgradableAsyncStream<S, SU>
+where
+    SU: PollDowngrader<S>,
+{
+    pub fn downgrade_required(&self) -> bool {
+        match &self.inner {
+            Inner::Upgraded(_, grader) => grader.downgrade_required(),
+            _ => false,
+        }
+    }
+
+    pub fn downgrade(&mut self) -> DowngradeFuture<'_, S, SU> {
+        DowngradeFuture { stream: self }
+    }
+
+    pub fn poll_downgrade(&mut self, cx: &mut Context) -> Poll<Result<(), UpgradeError>> {
+        self.inner.poll_downgrade_with(
+            cx,
+            |grader| grader.downgrade_required(),
+            |mut grader, output| {
+                grader.start_downgrade(output);
+                grader
+            },
+            |mut grader, cx| match grader.poll_downgrade(cx) {
+                Poll::Pending => Err(grader),
+                Poll::Ready(ret) => Ok((grader, ret)),
+            },
+            |grader| grader.recover_output(),
+        )
+    }
+}
+
+pub struct UpgradeFuture<'a, S, SU>
+where
+    SU: PollUpgrader<S>,
+{
+    stream: &'a mut PollUpgradableAsyncStream<S, SU>,
+}
+
+impl<'a, S, SU> Future for UpgradeFuture<'a, S, SU>
+where
+    SU: PollUpgrader<S>,
+{
+    type Output = Result<(), UpgradeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().stream.poll_upgrade(cx)
+    }
+}
+
+pub struct DowngradeFuture<'a, S, SU>
+where
+    SU: PollUpgrader<S>,
+{
+    stream: &'a mut PollUpgradableAsyncStream<S, SU>,
+}
+
+impl<'a, S, SU> Future for DowngradeFuture<'a, S, SU>
+where
+    SU: PollDowngrader<S>,
+{
+    type Output = Result<(), UpgradeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().stream.poll_downgrade(cx)
+    }
+}
+
+upgradable_async_stream_io!(PollUpgradableAsyncStream, PollUpgrader);
@@ -0,0 +1,370 @@
+use std::io;
+
+use async_trait::async_trait;
+
+use crate::http_connect::HttpConnectResponse;
+
+//
+//
+//
+/*
+Answers a 407 from the proxy. Returns the Proxy-Authorization value for the next attempt, or None
+to give up, in which case the 407 is returned as the error.
+
+Implement this for schemes the crate doesn't ship, like NTLM or Negotiate. A multi-leg scheme is
+called once per 407, with the challenge of that leg.
+*/
+pub trait ProxyAuthenticator: Send {
+    fn authorize(
+        &mut self,
+        method: &str,
+        uri: &str,
+        response: &HttpConnectResponse,
+    ) -> io::Result<Option<String>>;
+
+    // Called before each CONNECT, so what was tried on the previous one doesn't carry over.
+    fn reset(&mut self) {}
+}
+
+//
+//
+//
+/*
+Opens a new connection to the proxy, used when the proxy closes the connection after a 407.
+Returning None gives up.
+*/
+#[async_trait]
+pub trait ProxyConnector<S> {
+    async fn connect(&mut self) -> io::Result<Option<S>>;
+}
+
+#[async_trait]
+impl<S> ProxyConnector<S> for ()
+where
+    S: Send + 'static,
+{
+    async fn connect(&mut self) -> io::Result<Option<S>> {
+        Ok(None)
+    }
+}
+
+//
+//
+//
+#[cfg(feature = "proxy_auth")]
+pub use self::schemes::{BasicAuth, DigestAuth};
+
+#[cfg(feature = "proxy_auth")]
+mod schemes {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use md5::Md5;
+    use sha2::{Digest, Sha256};
+
+    use super::ProxyAuthenticator;
+    use crate::http_connect::HttpConnectResponse;
+
+    fn challenges<'a>(
+        response: &'a HttpConnectResponse,
+        scheme: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        response
+            .headers("Proxy-Authenticate")
+            .filter_map(move |value| {
+                let value = value.trim_start();
+                match value.find(' ') {
+                    Some(i) if value[..i].eq_ignore_ascii_case(scheme) => Some(&value[i + 1..]),
+                    None if value.eq_ignore_ascii_case(scheme) => Some(""),
+                    _ => None,
+                }
+            })
+    }
+
+    //
+    //
+    //
+    #[derive(Debug, Clone)]
+    pub struct BasicAuth {
+        username: String,
+        password: String,
+        tried: bool,
+    }
+
+    impl BasicAuth {
+        pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+            Self {
+                username: username.into(),
+                password: password.into(),
+                tried: false,
+            }
+        }
+    }
+
+    impl ProxyAuthenticator for BasicAuth {
+        fn authorize(
+            &mut self,
+            _method: &str,
+            _uri: &str,
+            response: &HttpConnectResponse,
+        ) -> io::Result<Option<String>> {
+            // Some proxies leave out Proxy-Authenticate, assume Basic then.
+            let offered = response.header("Proxy-Authenticate").is_none()
+                || challenges(response, "Basic").next().is_some();
+            if self.tried || !offered {
+                return Ok(None);
+            }
+            self.tried = true;
+
+            let credentials = format!("{}:{}", self.username, self.password);
+            Ok(Some(format!("Basic {}", base64::encode(credentials))))
+        }
+
+        fn reset(&mut self) {
+            self.tried = false;
+        }
+    }
+
+    //
+    //
+    //
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Algorithm {
+        Md5,
+        Md5Sess,
+        Sha256,
+        Sha256Sess,
+    }
+
+    impl Algorithm {
+        fn parse(s: Option<&str>) -> Option<Self> {
+            match s {
+                None => Some(Self::Md5),
+                Some(s) if s.eq_ignore_ascii_case("MD5") => Some(Self::Md5),
+                Some(s) if s.eq_ignore_ascii_case("MD5-sess") => Some(Self::Md5Sess),
+                Some(s) if s.eq_ignore_ascii_case("SHA-256") => Some(Self::Sha256),
+                Some(s) if s.eq_ignore_ascii_case("SHA-256-sess") => Some(Self::Sha256Sess),
+                Some(_) => None,
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                Self::Md5 => "MD5",
+                Self::Md5Sess => "MD5-sess",
+                Self::Sha256 => "SHA-256",
+                Self::Sha256Sess => "SHA-256-sess",
+            }
+        }
+
+        fn is_sha256(&self) -> bool {
+            matches!(self, Self::Sha256 | Self::Sha256Sess)
+        }
+
+        fn is_sess(&self) -> bool {
+            matches!(self, Self::Md5Sess | Self::Sha256Sess)
+        }
+
+        fn hash(&self, data: &str) -> String {
+            let digest = if self.is_sha256() {
+                Sha256::digest(data.as_bytes()).to_vec()
+            } else {
+                Md5::digest(data.as_bytes()).to_vec()
+            };
+            digest.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct Challenge {
+        realm: String,
+        nonce: String,
+        opaque: Option<String>,
+        algorithm: Algorithm,
+        qop_auth: bool,
+        stale: bool,
+    }
+
+    impl Challenge {
+        fn parse(s: &str) -> Option<Self> {
+            let params = parse_params(s);
+            let get = |name: &str| {
+                params
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.as_str())
+            };
+
+            let qop = get("qop");
+            Some(Self {
+                realm: get("realm")?.to_owned(),
+                nonce: get("nonce")?.to_owned(),
+                opaque: get("opaque").map(ToOwned::to_owned),
+                algorithm: Algorithm::parse(get("algorithm"))?,
+                qop_auth: qop
+                    .map(|qop| {
+                        qop.split(',')
+                            .any(|v| v.trim().eq_ignore_ascii_case("auth"))
+                    })
+                    .unwrap_or(false),
+                stale: get("stale")
+                    .map(|v| v.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+            })
+        }
+    }
+
+    // name=value and name="quoted, value" pairs separated by commas.
+    fn parse_params(s: &str) -> Vec<(String, String)> {
+        let mut params = vec![];
+        let mut chars = s.chars().peekable();
+        loop {
+            while let Some(c) = chars.peek() {
+                if *c == ',' || c.is_whitespace() {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            let mut name = String::new();
+            for c in chars.by_ref() {
+                if c == '=' {
+                    break;
+                }
+                name.push(c);
+            }
+            if name.is_empty() {
+                return params;
+            }
+
+            let mut value = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        c => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.peek() {
+                    if *c == ',' {
+                        break;
+                    }
+                    value.push(*c);
+                    chars.next();
+                }
+            }
+            params.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct DigestAuth {
+        username: String,
+        password: String,
+        cnonce: Option<String>,
+        answered: Option<String>,
+    }
+
+    impl DigestAuth {
+        pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+            Self {
+                username: username.into(),
+                password: password.into(),
+                cnonce: None,
+                answered: None,
+            }
+        }
+
+        // Fixes the client nonce, otherwise a fresh one is made for every challenge.
+        pub fn with_cnonce(mut self, cnonce: impl Into<String>) -> Self {
+            self.cnonce = Some(cnonce.into());
+            self
+        }
+
+        fn make_cnonce(&self, algorithm: Algorithm) -> String {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+            if let Some(cnonce) = &self.cnonce {
+                return cnonce.to_owned();
+            }
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let mut cnonce = algorithm.hash(&format!("{}:{}:{:p}", nanos, count, self));
+            cnonce.truncate(16);
+            cnonce
+        }
+    }
+
+    impl ProxyAuthenticator for DigestAuth {
+        fn authorize(
+            &mut self,
+            method: &str,
+            uri: &str,
+            response: &HttpConnectResponse,
+        ) -> io::Result<Option<String>> {
+            let challenge = challenges(response, "Digest")
+                .filter_map(Challenge::parse)
+                .max_by_key(|challenge| challenge.algorithm.is_sha256());
+            let challenge = match challenge {
+                Some(challenge) => challenge,
+                None => return Ok(None),
+            };
+            // The credentials were wrong, unless the proxy only wants a new nonce.
+            if self.answered.is_some() && !challenge.stale {
+                return Ok(None);
+            }
+
+            let algorithm = challenge.algorithm;
+            let cnonce = self.make_cnonce(algorithm);
+            let nc = "00000001";
+
+            let mut ha1 = algorithm.hash(&format!(
+                "{}:{}:{}",
+                self.username, challenge.realm, self.password
+            ));
+            if algorithm.is_sess() {
+                ha1 = algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+            }
+            let ha2 = algorithm.hash(&format!("{}:{}", method, uri));
+            let digest = if challenge.qop_auth {
+                algorithm.hash(&format!(
+                    "{}:{}:{}:{}:auth:{}",
+                    ha1, challenge.nonce, nc, cnonce, ha2
+                ))
+            } else {
+                algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, ha2))
+            };
+
+            let mut value = format!(
+                r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+                self.username,
+                challenge.realm,
+                challenge.nonce,
+                uri,
+                algorithm.name(),
+                digest
+            );
+            if challenge.qop_auth {
+                value.push_str(&format!(r#", qop=auth, nc={}, cnonce="{}""#, nc, cnonce));
+            }
+            if let Some(opaque) = &challenge.opaque {
+                value.push_str(&format!(r#", opaque="{}""#, opaque));
+            }
+
+            self.answered = Some(challenge.nonce);
+            Ok(Some(value))
+        }
+
+        fn reset(&mut self) {
+            self.answered = None;
+        }
+    }
+}
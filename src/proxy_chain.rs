@@ -0,0 +1,222 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyHopStage {
+    Tls,
+    Tunnel,
+}
+
+impl fmt::Display for ProxyHopStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tls => write!(f, "tls"),
+            Self::Tunnel => write!(f, "tunnel"),
+        }
+    }
+}
+
+//
+//
+//
+#[derive(Debug)]
+pub struct ProxyChainError {
+    hop: usize,
+    stage: ProxyHopStage,
+    err: io::Error,
+}
+
+impl ProxyChainError {
+    pub(crate) fn new(hop: usize, stage: ProxyHopStage, err: io::Error) -> Self {
+        Self { hop, stage, err }
+    }
+
+    // Zero based, in the order the hops were added.
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    pub fn stage(&self) -> ProxyHopStage {
+        self.stage
+    }
+
+    pub fn get_ref(&self) -> &io::Error {
+        &self.err
+    }
+
+    pub fn into_inner(self) -> io::Error {
+        self.err
+    }
+}
+
+impl fmt::Display for ProxyChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "proxy hop {} {} failed: {}",
+            self.hop, self.stage, self.err
+        )
+    }
+}
+
+impl error::Error for ProxyChainError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+impl From<ProxyChainError> for io::Error {
+    fn from(err: ProxyChainError) -> Self {
+        io::Error::new(err.err.kind(), err)
+    }
+}
+
+//
+//
+//
+macro_rules! proxy_chain {
+    () => {
+        use std::io;
+
+        use async_trait::async_trait;
+
+        use super::{ProxyChainError, ProxyHopStage};
+        use crate::tls::TlsClientUpgrader;
+        use crate::upgradable::Upgrader;
+
+        struct Hop {
+            tls_upgrader: Option<BoxUpgrader<BoxedAsyncStream>>,
+            tunnel: BoxUpgrader<BoxedAsyncStream>,
+        }
+
+        /*
+        Each hop's tunnel is spoken to the proxy reached so far and opens the way to the next one,
+        the last tunnel opens the way to the target. With TLS the hop's proxy is first talked to
+        over TLS, like an https:// proxy.
+        */
+        #[derive(Default)]
+        pub struct ProxyChain {
+            hops: Vec<Hop>,
+            failed_hop: Option<usize>,
+        }
+
+        impl ProxyChain {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            pub fn hop<U>(mut self, tunnel: U) -> Self
+            where
+                U: Upgrader<BoxedAsyncStream> + Send + 'static,
+                U::Output: AsyncRead + AsyncWrite + Send + 'static,
+            {
+                self.hops.push(Hop {
+                    tls_upgrader: None,
+                    tunnel: BoxUpgrader::new(tunnel),
+                });
+                self
+            }
+
+            pub fn tls_hop<TU, U>(mut self, tls_upgrader: TU, tunnel: U) -> Self
+            where
+                TU: TlsClientUpgrader<BoxedAsyncStream> + Send + 'static,
+                TU::Output: AsyncRead + AsyncWrite + Send + 'static,
+                U: Upgrader<BoxedAsyncStream> + Send + 'static,
+                U::Output: AsyncRead + AsyncWrite + Send + 'static,
+            {
+                self.hops.push(Hop {
+                    tls_upgrader: Some(BoxUpgrader::new(tls_upgrader)),
+                    tunnel: BoxUpgrader::new(tunnel),
+                });
+                self
+            }
+
+            pub fn len(&self) -> usize {
+                self.hops.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.hops.is_empty()
+            }
+
+            // Set by a failed upgrade, cleared by the next one.
+            pub fn failed_hop(&self) -> Option<usize> {
+                self.failed_hop
+            }
+
+            pub async fn connect<S>(
+                &mut self,
+                stream: S,
+            ) -> Result<BoxedAsyncStream, ProxyChainError>
+            where
+                S: AsyncRead + AsyncWrite + Send + 'static,
+            {
+                self.failed_hop = None;
+
+                let mut stream: BoxedAsyncStream = Box::pin(stream);
+                for (i, hop) in self.hops.iter_mut().enumerate() {
+                    let ret = async {
+                        if let Some(tls_upgrader) = &mut hop.tls_upgrader {
+                            stream = tls_upgrader
+                                .upgrade(stream)
+                                .await
+                                .map_err(|err| (ProxyHopStage::Tls, err))?;
+                        }
+                        hop.tunnel
+                            .upgrade(stream)
+                            .await
+                            .map_err(|err| (ProxyHopStage::Tunnel, err))
+                    }
+                    .await;
+                    stream = match ret {
+                        Ok(stream) => stream,
+                        Err((stage, err)) => {
+                            self.failed_hop = Some(i);
+                            return Err(ProxyChainError::new(i, stage, err));
+                        }
+                    };
+                }
+                Ok(stream)
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for ProxyChain
+        where
+            S: AsyncRead + AsyncWrite + Send + 'static,
+        {
+            type Output = BoxedAsyncStream;
+            async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+                self.connect(stream).await.map_err(Into::into)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::boxed::futures_io::{BoxUpgrader, BoxedAsyncStream};
+
+    proxy_chain!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::boxed::tokio_io::{BoxUpgrader, BoxedAsyncStream};
+
+    proxy_chain!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::boxed::tokio1_io::{BoxUpgrader, BoxedAsyncStream};
+
+    proxy_chain!();
+}
@@ -0,0 +1,642 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str;
+
+use crate::io_util::{read_exact, write_all, AsyncIo};
+
+// ref https://www.haproxy.org/download/2.3/doc/proxy-protocol.txt
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProxyProtocolError {
+    InvalidSignature,
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    UnsupportedCommand(u8),
+    MixedAddressFamilies,
+    TlvsRequireV2,
+    HeaderTooLong,
+}
+
+impl fmt::Display for ProxyProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::InvalidHeader => write!(f, "invalid header"),
+            Self::UnsupportedVersion(ver) => write!(f, "unsupported version {}", ver),
+            Self::UnsupportedCommand(cmd) => write!(f, "unsupported command {}", cmd),
+            Self::MixedAddressFamilies => {
+                write!(f, "source and destination address families differ")
+            }
+            Self::TlvsRequireV2 => write!(f, "tlvs require version 2"),
+            Self::HeaderTooLong => write!(f, "header too long"),
+        }
+    }
+}
+
+impl error::Error for ProxyProtocolError {}
+
+impl From<ProxyProtocolError> for io::Error {
+    fn from(err: ProxyProtocolError) -> Self {
+        let kind = match err {
+            ProxyProtocolError::MixedAddressFamilies
+            | ProxyProtocolError::TlvsRequireV2
+            | ProxyProtocolError::HeaderTooLong => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocolVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyProtocolTlv {
+    pub kind: u8,
+    pub value: Vec<u8>,
+}
+
+impl ProxyProtocolTlv {
+    pub const ALPN: u8 = 0x01;
+    pub const AUTHORITY: u8 = 0x02;
+    pub const CRC32C: u8 = 0x03;
+    pub const NOOP: u8 = 0x04;
+    pub const UNIQUE_ID: u8 = 0x05;
+    pub const SSL: u8 = 0x20;
+    pub const NETNS: u8 = 0x30;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyProtocolHeader {
+    pub version: ProxyProtocolVersion,
+    // (source, destination), None for v1 UNKNOWN, v2 LOCAL and v2 non-inet families.
+    pub addresses: Option<(SocketAddr, SocketAddr)>,
+    pub tlvs: Vec<ProxyProtocolTlv>,
+}
+
+const V1_PREFIX: &[u8] = b"PROXY";
+const V1_MAX_LEN: usize = 107;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\x00\r\nQUIT\n";
+const V2_VERSION: u8 = 0x20;
+const V2_CMD_LOCAL: u8 = 0x00;
+const V2_CMD_PROXY: u8 = 0x01;
+
+const V2_FAM_UNSPEC: u8 = 0x00;
+const V2_FAM_TCP4: u8 = 0x11;
+const V2_FAM_TCP6: u8 = 0x21;
+
+impl ProxyProtocolHeader {
+    pub fn new(
+        version: ProxyProtocolVersion,
+        source: impl Into<SocketAddr>,
+        destination: impl Into<SocketAddr>,
+    ) -> Self {
+        Self {
+            version,
+            addresses: Some((source.into(), destination.into())),
+            tlvs: vec![],
+        }
+    }
+
+    // v1 UNKNOWN or v2 LOCAL, e.g. for the proxy's own health checks.
+    pub fn local(version: ProxyProtocolVersion) -> Self {
+        Self {
+            version,
+            addresses: None,
+            tlvs: vec![],
+        }
+    }
+
+    pub fn with_tlv(mut self, kind: u8, value: impl Into<Vec<u8>>) -> Self {
+        self.tlvs.push(ProxyProtocolTlv {
+            kind,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn source(&self) -> Option<SocketAddr> {
+        self.addresses.map(|(source, _)| source)
+    }
+
+    pub fn destination(&self) -> Option<SocketAddr> {
+        self.addresses.map(|(_, destination)| destination)
+    }
+
+    pub fn tlv(&self, kind: u8) -> Option<&[u8]> {
+        self.tlvs
+            .iter()
+            .find(|tlv| tlv.kind == kind)
+            .map(|tlv| tlv.value.as_slice())
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        if let Some((source, destination)) = self.addresses {
+            if source.is_ipv4() != destination.is_ipv4() {
+                return Err(ProxyProtocolError::MixedAddressFamilies.into());
+            }
+        }
+
+        match self.version {
+            ProxyProtocolVersion::V1 => {
+                if !self.tlvs.is_empty() {
+                    return Err(ProxyProtocolError::TlvsRequireV2.into());
+                }
+                let line = match self.addresses {
+                    Some((source, destination)) => format!(
+                        "PROXY {} {} {} {} {}\r\n",
+                        if source.is_ipv4() { "TCP4" } else { "TCP6" },
+                        source.ip(),
+                        destination.ip(),
+                        source.port(),
+                        destination.port()
+                    ),
+                    None => "PROXY UNKNOWN\r\n".to_owned(),
+                };
+                Ok(line.into_bytes())
+            }
+            ProxyProtocolVersion::V2 => {
+                let mut payload = vec![];
+                let (cmd, fam) = match self.addresses {
+                    Some((source, destination)) => {
+                        let fam = match (source.ip(), destination.ip()) {
+                            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                                payload.extend_from_slice(&src.octets());
+                                payload.extend_from_slice(&dst.octets());
+                                V2_FAM_TCP4
+                            }
+                            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                                payload.extend_from_slice(&src.octets());
+                                payload.extend_from_slice(&dst.octets());
+                                V2_FAM_TCP6
+                            }
+                            _ => unreachable!(),
+                        };
+                        payload.extend_from_slice(&source.port().to_be_bytes());
+                        payload.extend_from_slice(&destination.port().to_be_bytes());
+                        (V2_CMD_PROXY, fam)
+                    }
+                    None => (V2_CMD_LOCAL, V2_FAM_UNSPEC),
+                };
+                for tlv in &self.tlvs {
+                    if tlv.value.len() > u16::MAX as usize {
+                        return Err(ProxyProtocolError::HeaderTooLong.into());
+                    }
+                    payload.push(tlv.kind);
+                    payload.extend_from_slice(&(tlv.value.len() as u16).to_be_bytes());
+                    payload.extend_from_slice(&tlv.value);
+                }
+                if payload.len() > u16::MAX as usize {
+                    return Err(ProxyProtocolError::HeaderTooLong.into());
+                }
+
+                let mut buf = V2_SIGNATURE.to_vec();
+                buf.push(V2_VERSION | cmd);
+                buf.push(fam);
+                buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+                buf.extend_from_slice(&payload);
+                Ok(buf)
+            }
+        }
+    }
+
+    /*
+    Reads exactly the header and nothing more, v1 is read byte by byte up to its CRLF and v2 by
+    its length field, so whatever the client sent after it stays in the stream.
+    */
+    pub(crate) async fn read<T>(io: &mut T) -> io::Result<Self>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        // Both versions are longer than this.
+        let mut buf = vec![0; V1_PREFIX.len()];
+        read_exact(io, &mut buf).await?;
+
+        if buf == V1_PREFIX {
+            loop {
+                let mut b = [0; 1];
+                read_exact(io, &mut b).await?;
+                buf.push(b[0]);
+                if buf.ends_with(b"\r\n") {
+                    break;
+                }
+                if buf.len() >= V1_MAX_LEN {
+                    return Err(ProxyProtocolError::InvalidHeader.into());
+                }
+            }
+            Self::parse_v1(&buf[..buf.len() - 2])
+                .ok_or_else(|| ProxyProtocolError::InvalidHeader.into())
+        } else if buf == V2_SIGNATURE[..buf.len()] {
+            buf.resize(V2_SIGNATURE.len() + 4, 0);
+            read_exact(io, &mut buf[V1_PREFIX.len()..]).await?;
+            if &buf[..V2_SIGNATURE.len()] != V2_SIGNATURE {
+                return Err(ProxyProtocolError::InvalidSignature.into());
+            }
+
+            let ver_cmd = buf[V2_SIGNATURE.len()];
+            let fam = buf[V2_SIGNATURE.len() + 1];
+            let len =
+                u16::from_be_bytes([buf[V2_SIGNATURE.len() + 2], buf[V2_SIGNATURE.len() + 3]]);
+            if ver_cmd & 0xF0 != V2_VERSION {
+                return Err(ProxyProtocolError::UnsupportedVersion(ver_cmd >> 4).into());
+            }
+            let cmd = ver_cmd & 0x0F;
+            if cmd != V2_CMD_LOCAL && cmd != V2_CMD_PROXY {
+                return Err(ProxyProtocolError::UnsupportedCommand(cmd).into());
+            }
+
+            let mut payload = vec![0; len as usize];
+            read_exact(io, &mut payload).await?;
+            Self::parse_v2(cmd, fam, &payload)
+                .ok_or_else(|| ProxyProtocolError::InvalidHeader.into())
+        } else {
+            Err(ProxyProtocolError::InvalidSignature.into())
+        }
+    }
+
+    fn parse_v1(line: &[u8]) -> Option<Self> {
+        let line = str::from_utf8(line).ok()?;
+        let mut parts = line.split(' ');
+        if parts.next()? != "PROXY" {
+            return None;
+        }
+
+        match parts.next()? {
+            // The rest of the line is to be ignored.
+            "UNKNOWN" => Some(Self::local(ProxyProtocolVersion::V1)),
+            proto @ "TCP4" | proto @ "TCP6" => {
+                let (source, destination): (IpAddr, IpAddr) = if proto == "TCP4" {
+                    (
+                        parts.next()?.parse::<Ipv4Addr>().ok()?.into(),
+                        parts.next()?.parse::<Ipv4Addr>().ok()?.into(),
+                    )
+                } else {
+                    (
+                        parts.next()?.parse::<Ipv6Addr>().ok()?.into(),
+                        parts.next()?.parse::<Ipv6Addr>().ok()?.into(),
+                    )
+                };
+                let source_port = parts.next()?.parse::<u16>().ok()?;
+                let destination_port = parts.next()?.parse::<u16>().ok()?;
+                if parts.next().is_some() {
+                    return None;
+                }
+                Some(Self::new(
+                    ProxyProtocolVersion::V1,
+                    (source, source_port),
+                    (destination, destination_port),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_v2(cmd: u8, fam: u8, payload: &[u8]) -> Option<Self> {
+        // By the address family, the transport protocol in the low bits doesn't matter here.
+        let addr_len = match fam >> 4 {
+            0x0 => 0,
+            0x1 => 12,
+            0x2 => 36,
+            0x3 => 216,
+            _ => return None,
+        };
+        if payload.len() < addr_len {
+            return None;
+        }
+        let (addr, mut rest) = payload.split_at(addr_len);
+
+        let addresses = match (cmd, addr_len) {
+            (V2_CMD_PROXY, 12) => {
+                let source = Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3]);
+                let destination = Ipv4Addr::new(addr[4], addr[5], addr[6], addr[7]);
+                Some((
+                    SocketAddr::new(source.into(), u16::from_be_bytes([addr[8], addr[9]])),
+                    SocketAddr::new(destination.into(), u16::from_be_bytes([addr[10], addr[11]])),
+                ))
+            }
+            (V2_CMD_PROXY, 36) => {
+                let mut source = [0; 16];
+                source.copy_from_slice(&addr[..16]);
+                let mut destination = [0; 16];
+                destination.copy_from_slice(&addr[16..32]);
+                Some((
+                    SocketAddr::new(
+                        Ipv6Addr::from(source).into(),
+                        u16::from_be_bytes([addr[32], addr[33]]),
+                    ),
+                    SocketAddr::new(
+                        Ipv6Addr::from(destination).into(),
+                        u16::from_be_bytes([addr[34], addr[35]]),
+                    ),
+                ))
+            }
+            _ => None,
+        };
+
+        let mut tlvs = vec![];
+        while !rest.is_empty() {
+            if rest.len() < 3 {
+                return None;
+            }
+            let len = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+            if rest.len() < 3 + len {
+                return None;
+            }
+            tlvs.push(ProxyProtocolTlv {
+                kind: rest[0],
+                value: rest[3..3 + len].to_vec(),
+            });
+            rest = &rest[3 + len..];
+        }
+
+        Some(Self {
+            version: ProxyProtocolVersion::V2,
+            addresses,
+            tlvs,
+        })
+    }
+
+    pub(crate) async fn write<T>(&self, io: &mut T) -> io::Result<()>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        write_all(io, &self.to_bytes()?).await
+    }
+}
+
+//
+//
+//
+macro_rules! proxy_protocol_upgrader {
+    () => {
+        use std::io;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        use async_trait::async_trait;
+
+        use super::ProxyProtocolHeader;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        #[derive(Debug, Clone)]
+        pub struct ProxyProtocolClientUpgrader {
+            header: ProxyProtocolHeader,
+        }
+
+        impl ProxyProtocolClientUpgrader {
+            pub fn new(header: ProxyProtocolHeader) -> Self {
+                Self { header }
+            }
+
+            pub fn header(&self) -> &ProxyProtocolHeader {
+                &self.header
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for ProxyProtocolClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            type Output = S;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                self.header.write(&mut Compat(&mut stream)).await?;
+                Ok(stream)
+            }
+        }
+
+        impl<S> UpgraderExtRefer<S> for ProxyProtocolClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output
+            }
+        }
+
+        impl<S> UpgraderExtIntoStream<S> for ProxyProtocolClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+
+        //
+        //
+        //
+        /*
+        Accepts either version. The application bytes following the header are left unread, the
+        output stream carries the header.
+        */
+        #[derive(Debug, Clone, Default)]
+        pub struct ProxyProtocolServerUpgrader {
+            header: Option<ProxyProtocolHeader>,
+        }
+
+        impl ProxyProtocolServerUpgrader {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            pub fn header(&self) -> Option<&ProxyProtocolHeader> {
+                self.header.as_ref()
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for ProxyProtocolServerUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            type Output = ProxyProtocolAsyncStream<S>;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                let header = ProxyProtocolHeader::read(&mut Compat(&mut stream)).await?;
+                self.header = Some(header.clone());
+                Ok(ProxyProtocolAsyncStream {
+                    inner: stream,
+                    header,
+                })
+            }
+        }
+
+        impl<S> UpgraderExtRefer<S> for ProxyProtocolServerUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output.get_ref()
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output.get_mut()
+            }
+        }
+
+        impl<S> UpgraderExtIntoStream<S> for ProxyProtocolServerUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output.into_inner())
+            }
+        }
+
+        //
+        #[derive(Debug)]
+        pub struct ProxyProtocolAsyncStream<S> {
+            inner: S,
+            header: ProxyProtocolHeader,
+        }
+
+        impl<S> ProxyProtocolAsyncStream<S> {
+            // The addresses and TLVs the proxy sent for this connection.
+            pub fn header(&self) -> &ProxyProtocolHeader {
+                &self.header
+            }
+
+            pub fn get_ref(&self) -> &S {
+                &self.inner
+            }
+
+            pub fn get_mut(&mut self) -> &mut S {
+                &mut self.inner
+            }
+
+            pub fn into_inner(self) -> S {
+                self.inner
+            }
+
+            pub fn into_parts(self) -> (S, ProxyProtocolHeader) {
+                (self.inner, self.header)
+            }
+        }
+
+        impl<S> AsyncRead for ProxyProtocolAsyncStream<S>
+        where
+            S: AsyncRead + Unpin,
+        {
+            poll_read!();
+        }
+
+        impl<S> AsyncWrite for ProxyProtocolAsyncStream<S>
+        where
+            S: AsyncWrite + Unpin,
+        {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+            }
+
+            poll_close!();
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    macro_rules! poll_read {
+        () => {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+            }
+        };
+    }
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_close(cx)
+            }
+        };
+    }
+
+    proxy_protocol_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    macro_rules! poll_read {
+        () => {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+            }
+        };
+    }
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+            }
+        };
+    }
+
+    proxy_protocol_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    macro_rules! poll_read {
+        () => {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+            }
+        };
+    }
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+            }
+        };
+    }
+
+    proxy_protocol_upgrader!();
+}
@@ -0,0 +1,465 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+
+use crate::socks5::TargetAddr;
+
+// ref https://curl.se/docs/manpage.html#ENVIRONMENT
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyScheme {
+    Http,
+    Https,
+    // The target is resolved by the client.
+    Socks5,
+    // The target is resolved by the proxy.
+    Socks5h,
+}
+
+impl ProxyScheme {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Self::Https => 443,
+            _ => 1080,
+        }
+    }
+}
+
+impl FromStr for ProxyScheme {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "http" => Ok(Self::Http),
+            "https" => Ok(Self::Https),
+            "socks5" => Ok(Self::Socks5),
+            "socks5h" => Ok(Self::Socks5h),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported proxy scheme {}", s),
+            )),
+        }
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyUrl {
+    pub scheme: ProxyScheme,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ProxyUrl {
+    pub fn remote_dns(&self) -> bool {
+        self.scheme != ProxyScheme::Socks5
+    }
+}
+
+// Like curl, a missing scheme means http:// and anything after the authority is ignored.
+impl FromStr for ProxyUrl {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid proxy url {}", s),
+            )
+        };
+
+        let s = s.trim();
+        let (scheme, rest) = match s.find("://") {
+            Some(i) => (s[..i].parse()?, &s[i + 3..]),
+            None => (ProxyScheme::Http, s),
+        };
+        let authority = match rest.find(&['/', '?', '#'][..]) {
+            Some(i) => &rest[..i],
+            None => rest,
+        };
+
+        let (userinfo, host_port) = match authority.rfind('@') {
+            Some(i) => (Some(&authority[..i]), &authority[i + 1..]),
+            None => (None, authority),
+        };
+        let (username, password) = match userinfo {
+            Some(userinfo) => {
+                let mut parts = userinfo.splitn(2, ':');
+                let username = percent_decode(parts.next().unwrap_or_default())?;
+                let password = parts.next().map(percent_decode).transpose()?;
+                (Some(username), password)
+            }
+            None => (None, None),
+        };
+
+        let (host, port) = if host_port.starts_with('[') {
+            let end = host_port.find(']').ok_or_else(invalid)?;
+            let port = match &host_port[end + 1..] {
+                "" => None,
+                port if port.starts_with(':') => Some(&port[1..]),
+                _ => return Err(invalid()),
+            };
+            (&host_port[1..end], port)
+        } else {
+            match host_port.rfind(':') {
+                Some(i) => (&host_port[..i], Some(&host_port[i + 1..])),
+                None => (host_port, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = match port {
+            Some(port) if !port.is_empty() => port.parse().map_err(|_| invalid())?,
+            _ => scheme.default_port(),
+        };
+
+        Ok(Self {
+            scheme,
+            host: host.to_owned(),
+            port,
+            username,
+            password,
+        })
+    }
+}
+
+fn percent_decode(s: &str) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid percent-encoding");
+
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [
+                iter.next().ok_or_else(invalid)?,
+                iter.next().ok_or_else(invalid)?,
+            ];
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+//
+//
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NoProxyEntry {
+    Domain(String),
+    Ip(IpAddr, u8),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NoProxy {
+    all: bool,
+    entries: Vec<NoProxyEntry>,
+}
+
+impl NoProxy {
+    /*
+    Comma or space separated. "*" matches every host, a domain matches itself and its subdomains,
+    with or without a leading dot, and an IP address may carry a CIDR prefix length.
+    */
+    pub fn parse(s: &str) -> Self {
+        let mut no_proxy = Self::default();
+        for entry in s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+        {
+            if entry == "*" {
+                no_proxy.all = true;
+                continue;
+            }
+
+            let (addr, prefix) = match entry.find('/') {
+                Some(i) => (&entry[..i], Some(&entry[i + 1..])),
+                None => (entry, None),
+            };
+            let addr = addr.trim_start_matches('[').trim_end_matches(']');
+            if let Ok(ip) = addr.parse::<IpAddr>() {
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                match prefix.map(|prefix| prefix.parse::<u8>()) {
+                    None => no_proxy.entries.push(NoProxyEntry::Ip(ip, max)),
+                    Some(Ok(prefix)) if prefix <= max => {
+                        no_proxy.entries.push(NoProxyEntry::Ip(ip, prefix))
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            let domain = entry.trim_start_matches('.').trim_end_matches('.');
+            if !domain.is_empty() {
+                no_proxy
+                    .entries
+                    .push(NoProxyEntry::Domain(domain.to_ascii_lowercase()));
+            }
+        }
+        no_proxy
+    }
+
+    pub fn matches(&self, host: &str) -> bool {
+        if self.all {
+            return true;
+        }
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let ip = host.parse::<IpAddr>().ok();
+        self.entries.iter().any(|entry| match (entry, ip) {
+            (NoProxyEntry::Ip(net, prefix), Some(ip)) => ip_in_net(ip, *net, *prefix),
+            (NoProxyEntry::Domain(domain), _) => {
+                host == *domain
+                    || (host.ends_with(domain.as_str())
+                        && host[..host.len() - domain.len()].ends_with('.'))
+            }
+            _ => false,
+        })
+    }
+}
+
+fn ip_in_net(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+//
+//
+//
+#[derive(Debug, Clone, Default)]
+pub struct ProxyResolver {
+    http: Option<ProxyUrl>,
+    https: Option<ProxyUrl>,
+    all: Option<ProxyUrl>,
+    no_proxy: NoProxy,
+}
+
+impl ProxyResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_http_proxy(mut self, proxy: ProxyUrl) -> Self {
+        self.http = Some(proxy);
+        self
+    }
+
+    pub fn with_https_proxy(mut self, proxy: ProxyUrl) -> Self {
+        self.https = Some(proxy);
+        self
+    }
+
+    pub fn with_all_proxy(mut self, proxy: ProxyUrl) -> Self {
+        self.all = Some(proxy);
+        self
+    }
+
+    pub fn with_no_proxy(mut self, no_proxy: NoProxy) -> Self {
+        self.no_proxy = no_proxy;
+        self
+    }
+
+    pub fn from_env() -> io::Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /*
+    Lowercase names win over uppercase ones and empty values count as unset. As in curl, only the
+    lowercase http_proxy is read, HTTP_PROXY may be set by a CGI request header.
+    */
+    pub fn from_vars<F>(vars: F) -> io::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |names: &[&str]| {
+            names
+                .iter()
+                .filter_map(|name| vars(name))
+                .find(|value| !value.trim().is_empty())
+        };
+        let proxy = |names: &[&str]| var(names).map(|value| value.parse()).transpose();
+
+        Ok(Self {
+            http: proxy(&["http_proxy"])?,
+            https: proxy(&["https_proxy", "HTTPS_PROXY"])?,
+            all: proxy(&["all_proxy", "ALL_PROXY"])?,
+            no_proxy: var(&["no_proxy", "NO_PROXY"])
+                .map(|value| NoProxy::parse(&value))
+                .unwrap_or_default(),
+        })
+    }
+
+    // scheme is the one of the target url, http or https.
+    pub fn resolve(&self, scheme: &str, host: &str) -> Option<&ProxyUrl> {
+        if self.no_proxy.matches(host) {
+            return None;
+        }
+        let proxy = if scheme.eq_ignore_ascii_case("https") {
+            self.https.as_ref()
+        } else if scheme.eq_ignore_ascii_case("http") {
+            self.http.as_ref()
+        } else {
+            None
+        };
+        proxy.or(self.all.as_ref())
+    }
+}
+
+fn target_addr(host: &str, port: u16) -> TargetAddr {
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => TargetAddr::Ip(SocketAddr::new(ip, port)),
+        Err(_) => TargetAddr::Domain(host.to_owned(), port),
+    }
+}
+
+// socks5:// leaves the name resolution to the client, the proxy only gets an IP. Blocks on it.
+fn socks5_target_addr(proxy: &ProxyUrl, host: &str, port: u16) -> io::Result<TargetAddr> {
+    match target_addr(host, port) {
+        TargetAddr::Domain(domain, port) if !proxy.remote_dns() => (domain.as_str(), port)
+            .to_socket_addrs()?
+            .next()
+            .map(TargetAddr::Ip)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no address for {}", domain),
+                )
+            }),
+        addr => Ok(addr),
+    }
+}
+
+//
+//
+//
+macro_rules! http_client_proxy {
+    () => {
+        use std::io;
+
+        use super::{socks5_target_addr, target_addr, ProxyResolver, ProxyScheme, ProxyUrl};
+        use crate::http::{HttpClientProxy, HttpClientProxyInner, HttpProxyKind};
+
+        pub type ResolvedHttpClientProxy<S, HTTU> =
+            HttpClientProxy<S, HTTU, HttpConnectTunnelGrader, Socks5ClientUpgrader>;
+
+        /*
+        Picks the proxy for the target and builds the matching HttpClientProxy. tls_upgrader is
+        only called for an https:// proxy.
+
+        socks5:// asks for the target to be resolved locally, a domain is resolved here (blocking)
+        and the proxy gets its first address, socks5h:// hands it to the proxy as is. Credentials
+        in an http(s):// proxy url are sent as Basic up front, which needs the proxy_auth feature,
+        without it they fail with InvalidInput.
+        */
+        pub fn resolve_http_client_proxy<S, HTTU, F>(
+            resolver: &ProxyResolver,
+            scheme: &str,
+            host: &str,
+            port: u16,
+            tls_upgrader: F,
+        ) -> io::Result<Option<ResolvedHttpClientProxy<S, HTTU>>>
+        where
+            F: FnOnce(&ProxyUrl) -> HTTU,
+        {
+            let proxy = match resolver.resolve(scheme, host) {
+                Some(proxy) => proxy,
+                None => return Ok(None),
+            };
+
+            let inner = match proxy.scheme {
+                ProxyScheme::Http => HttpClientProxyInner::Http(tunnel_grader(proxy, host, port)?),
+                ProxyScheme::Https => HttpClientProxyInner::Https(
+                    tls_upgrader(proxy),
+                    tunnel_grader(proxy, host, port)?,
+                ),
+                ProxyScheme::Socks5 | ProxyScheme::Socks5h => {
+                    let mut upgrader =
+                        Socks5ClientUpgrader::new(socks5_target_addr(proxy, host, port)?);
+                    if let Some(username) = &proxy.username {
+                        upgrader = upgrader.with_auth(
+                            username.to_owned(),
+                            proxy.password.to_owned().unwrap_or_default(),
+                        );
+                    }
+                    HttpClientProxyInner::Socks(upgrader, HttpProxyKind::Socks5)
+                }
+            };
+            Ok(Some(HttpClientProxy::with_inner(inner)))
+        }
+
+        fn tunnel_grader(
+            proxy: &ProxyUrl,
+            host: &str,
+            port: u16,
+        ) -> io::Result<HttpConnectTunnelGrader> {
+            let grader = HttpConnectTunnelGrader::new(target_addr(host, port));
+
+            #[cfg(feature = "proxy_auth")]
+            let grader = match &proxy.username {
+                Some(username) => grader.with_proxy_authorization(format!(
+                    "Basic {}",
+                    base64::encode(format!(
+                        "{}:{}",
+                        username,
+                        proxy.password.as_deref().unwrap_or_default()
+                    ))
+                )),
+                None => grader,
+            };
+            #[cfg(not(feature = "proxy_auth"))]
+            if proxy.username.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "proxy credentials require the proxy_auth feature",
+                ));
+            }
+
+            Ok(grader)
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use crate::http_connect::futures_io::HttpConnectTunnelGrader;
+    use crate::socks5::futures_io::Socks5ClientUpgrader;
+
+    http_client_proxy!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use crate::http_connect::tokio_io::HttpConnectTunnelGrader;
+    use crate::socks5::tokio_io::Socks5ClientUpgrader;
+
+    http_client_proxy!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use crate::http_connect::tokio1_io::HttpConnectTunnelGrader;
+    use crate::socks5::tokio1_io::Socks5ClientUpgrader;
+
+    http_client_proxy!();
+}
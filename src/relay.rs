@@ -0,0 +1,228 @@
+use std::io;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::io_util::{AsyncIo, PollFn};
+use crate::timeout::Timer;
+
+const BUF_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayCounts {
+    pub a_to_b: u64,
+    pub b_to_a: u64,
+}
+
+//
+//
+//
+struct Half {
+    buf: Box<[u8]>,
+    pos: usize,
+    cap: usize,
+    eof: bool,
+    need_flush: bool,
+    done: bool,
+    amt: u64,
+}
+
+impl Half {
+    fn new() -> Self {
+        Self {
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            cap: 0,
+            eof: false,
+            need_flush: false,
+            done: false,
+            amt: 0,
+        }
+    }
+
+    /*
+    Copies until the reader hits EOF, then closes the writer, so the peer sees the half-close
+    while the other direction keeps going.
+    */
+    fn poll_copy<R, W>(
+        &mut self,
+        cx: &mut Context,
+        reader: &mut R,
+        writer: &mut W,
+        progressed: &mut bool,
+    ) -> Poll<io::Result<()>>
+    where
+        R: AsyncIo + ?Sized,
+        W: AsyncIo + ?Sized,
+    {
+        loop {
+            if self.done {
+                return Poll::Ready(Ok(()));
+            }
+
+            if self.pos == self.cap && !self.eof {
+                match reader.poll_read(cx, &mut self.buf) {
+                    Poll::Ready(Ok(0)) => self.eof = true,
+                    Poll::Ready(Ok(n)) => {
+                        self.pos = 0;
+                        self.cap = n;
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => {
+                        // Nothing more to send for now, don't leave the written bytes buffered.
+                        if self.need_flush {
+                            match writer.poll_flush(cx) {
+                                Poll::Ready(Ok(())) => self.need_flush = false,
+                                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                                Poll::Pending => {}
+                            }
+                        }
+                        return Poll::Pending;
+                    }
+                }
+                *progressed = true;
+            }
+
+            while self.pos < self.cap {
+                match writer.poll_write(cx, &self.buf[self.pos..self.cap]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(n)) => {
+                        self.pos += n;
+                        self.amt += n as u64;
+                        self.need_flush = true;
+                        *progressed = true;
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            if self.eof {
+                match writer.poll_flush(cx) {
+                    Poll::Ready(Ok(())) => self.need_flush = false,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+                match writer.poll_close(cx) {
+                    Poll::Ready(Ok(())) => self.done = true,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        }
+    }
+}
+
+pub(crate) async fn relay_io<A, B, T>(
+    a: &mut A,
+    b: &mut B,
+    idle_timeout: Option<(&T, Duration)>,
+) -> io::Result<RelayCounts>
+where
+    A: AsyncIo + ?Sized,
+    B: AsyncIo + ?Sized,
+    T: Timer + ?Sized,
+{
+    let mut a_to_b = Half::new();
+    let mut b_to_a = Half::new();
+
+    let mut last_active = Instant::now();
+    let mut sleep = idle_timeout.map(|(timer, dur)| timer.sleep(dur));
+
+    PollFn(|cx: &mut Context| {
+        let mut progressed = false;
+        let a_to_b_ready = a_to_b.poll_copy(cx, a, b, &mut progressed)?.is_ready();
+        let b_to_a_ready = b_to_a.poll_copy(cx, b, a, &mut progressed)?.is_ready();
+        if a_to_b_ready && b_to_a_ready {
+            return Poll::Ready(Ok(RelayCounts {
+                a_to_b: a_to_b.amt,
+                b_to_a: b_to_a.amt,
+            }));
+        }
+
+        if progressed {
+            last_active = Instant::now();
+        }
+        if let (Some((timer, dur)), Some(sleep)) = (idle_timeout, sleep.as_mut()) {
+            while sleep.as_mut().poll(cx).is_ready() {
+                let elapsed = last_active.elapsed();
+                if elapsed >= dur {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "relay idle timeout",
+                    )));
+                }
+                *sleep = timer.sleep(dur - elapsed);
+            }
+        }
+
+        Poll::Pending
+    })
+    .await
+}
+
+//
+//
+//
+macro_rules! relay {
+    () => {
+        use std::io;
+        use std::time::Duration;
+
+        use super::{relay_io, RelayCounts};
+        use crate::timeout::Timer;
+
+        /*
+        Copies a to b and b to a at the same time until both sides reached EOF. Each direction
+        closes its writer as soon as its reader is done.
+        */
+        pub async fn relay<A, B>(a: &mut A, b: &mut B) -> io::Result<RelayCounts>
+        where
+            A: AsyncRead + AsyncWrite + Unpin,
+            B: AsyncRead + AsyncWrite + Unpin,
+        {
+            relay_io::<_, _, dyn Timer + Sync>(&mut Compat(a), &mut Compat(b), None).await
+        }
+
+        // Fails with TimedOut once neither direction moved a byte for idle.
+        pub async fn relay_with_idle_timeout<A, B, T>(
+            a: &mut A,
+            b: &mut B,
+            timer: &T,
+            idle: Duration,
+        ) -> io::Result<RelayCounts>
+        where
+            A: AsyncRead + AsyncWrite + Unpin,
+            B: AsyncRead + AsyncWrite + Unpin,
+            T: Timer + Sync + ?Sized,
+        {
+            relay_io(&mut Compat(a), &mut Compat(b), Some((timer, idle))).await
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    relay!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    relay!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    relay!();
+}
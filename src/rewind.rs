@@ -0,0 +1,372 @@
+/*
+AsyncRead, AsyncBufRead and peek are implemented once per io flavor, so every io flavor gets its
+own RewindAsyncStream and RewindUpgrader.
+*/
+macro_rules! rewind_async_stream {
+    () => {
+        use std::io;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        use async_trait::async_trait;
+
+        use crate::io_util::PollFn;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        const BUF_SIZE: usize = 8 * 1024;
+
+        /*
+        Replays the bytes pushed back with prepend or unread, and the ones looked at with peek,
+        before reading from the inner stream again.
+        */
+        #[derive(Debug)]
+        pub struct RewindAsyncStream<S> {
+            inner: S,
+            buf: Vec<u8>,
+            pos: usize,
+        }
+
+        impl<S> RewindAsyncStream<S> {
+            pub fn new(inner: S) -> Self {
+                Self {
+                    inner,
+                    buf: vec![],
+                    pos: 0,
+                }
+            }
+
+            pub fn prepend(mut self, buf: impl AsRef<[u8]>) -> Self {
+                self.unread(buf.as_ref());
+                self
+            }
+
+            // The bytes are read again before the ones already buffered.
+            pub fn unread(&mut self, buf: &[u8]) {
+                self.buf.drain(..self.pos);
+                self.pos = 0;
+                self.buf.splice(..0, buf.iter().cloned());
+            }
+
+            pub fn buffer(&self) -> &[u8] {
+                &self.buf[self.pos..]
+            }
+
+            pub fn get_ref(&self) -> &S {
+                &self.inner
+            }
+
+            pub fn get_mut(&mut self) -> &mut S {
+                &mut self.inner
+            }
+
+            pub fn into_parts(self) -> (S, Vec<u8>) {
+                let mut buf = self.buf;
+                buf.drain(..self.pos);
+                (self.inner, buf)
+            }
+
+            // Fails while there are bytes left to replay, they would be lost otherwise.
+            pub fn into_inner(self) -> io::Result<S> {
+                if self.pos < self.buf.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "rewind buffer not empty",
+                    ));
+                }
+                Ok(self.inner)
+            }
+
+            fn consume_buf(&mut self, amt: usize) {
+                self.pos = (self.pos + amt).min(self.buf.len());
+                if self.pos == self.buf.len() {
+                    self.buf.clear();
+                    self.pos = 0;
+                }
+            }
+        }
+
+        impl<S> RewindAsyncStream<S>
+        where
+            S: AsyncRead + Unpin,
+        {
+            // Reads until n bytes are buffered or EOF, without consuming any.
+            pub async fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+                while self.buf.len() - self.pos < n {
+                    let amt = PollFn(|cx: &mut Context| self.poll_fill(cx)).await?;
+                    if amt == 0 {
+                        break;
+                    }
+                }
+                let end = (self.pos + n).min(self.buf.len());
+                Ok(&self.buf[self.pos..end])
+            }
+        }
+
+        impl<S> AsyncWrite for RewindAsyncStream<S>
+        where
+            S: AsyncWrite + Unpin,
+        {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+            }
+
+            poll_close!();
+        }
+
+        impl<S> AsyncBufRead for RewindAsyncStream<S>
+        where
+            S: AsyncRead + Unpin,
+        {
+            fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+                let this = self.get_mut();
+                if this.pos == this.buf.len() {
+                    match this.poll_fill(cx) {
+                        Poll::Ready(Ok(_)) => {}
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }
+                Poll::Ready(Ok(&this.buf[this.pos..]))
+            }
+
+            fn consume(self: Pin<&mut Self>, amt: usize) {
+                self.get_mut().consume_buf(amt)
+            }
+        }
+
+        //
+        //
+        //
+        /*
+        Hands out the inner upgrader's output as a RewindAsyncStream, so that what follows the
+        upgrade can be peeked at and pushed back, e.g. to sniff the protocol spoken over it.
+        */
+        #[derive(Debug, Clone)]
+        pub struct RewindUpgrader<U> {
+            inner: U,
+        }
+
+        impl<U> RewindUpgrader<U> {
+            pub fn new(inner: U) -> Self {
+                Self { inner }
+            }
+
+            pub fn get_ref(&self) -> &U {
+                &self.inner
+            }
+
+            pub fn get_mut(&mut self) -> &mut U {
+                &mut self.inner
+            }
+
+            pub fn into_inner(self) -> U {
+                self.inner
+            }
+        }
+
+        #[async_trait]
+        impl<S, U> Upgrader<S> for RewindUpgrader<U>
+        where
+            S: Send + 'static,
+            U: Upgrader<S> + Send,
+        {
+            type Output = RewindAsyncStream<U::Output>;
+            async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+                self.inner.upgrade(stream).await.map(RewindAsyncStream::new)
+            }
+            fn upgrade_required(&self) -> bool {
+                self.inner.upgrade_required()
+            }
+            fn recover_stream(&mut self) -> Option<S> {
+                self.inner.recover_stream()
+            }
+        }
+
+        impl<S, U> UpgraderExtRefer<S> for RewindUpgrader<U>
+        where
+            S: Send + 'static,
+            U: UpgraderExtRefer<S> + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                U::get_ref(output.get_ref())
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                U::get_mut(output.get_mut())
+            }
+        }
+
+        // Fails while there are bytes left to replay, like RewindAsyncStream::into_inner.
+        impl<S, U> UpgraderExtIntoStream<S> for RewindUpgrader<U>
+        where
+            S: Send + 'static,
+            U: UpgraderExtIntoStream<S> + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                U::into_stream(output.into_inner()?)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_close(cx)
+            }
+        };
+    }
+
+    rewind_async_stream!();
+
+    impl<S> RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_fill(&mut self, cx: &mut Context) -> Poll<io::Result<usize>> {
+            let len = self.buf.len();
+            self.buf.resize(len + BUF_SIZE, 0);
+            let ret = Pin::new(&mut self.inner).poll_read(cx, &mut self.buf[len..]);
+            let n = match &ret {
+                Poll::Ready(Ok(n)) => *n,
+                _ => 0,
+            };
+            self.buf.truncate(len + n);
+            ret
+        }
+    }
+
+    impl<S> AsyncRead for RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if this.pos < this.buf.len() {
+                let n = buf.len().min(this.buf.len() - this.pos);
+                buf[..n].copy_from_slice(&this.buf[this.pos..this.pos + n]);
+                this.consume_buf(n);
+                return Poll::Ready(Ok(n));
+            }
+            Pin::new(&mut this.inner).poll_read(cx, buf)
+        }
+    }
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+            }
+        };
+    }
+
+    rewind_async_stream!();
+
+    impl<S> RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_fill(&mut self, cx: &mut Context) -> Poll<io::Result<usize>> {
+            let len = self.buf.len();
+            self.buf.resize(len + BUF_SIZE, 0);
+            let ret = Pin::new(&mut self.inner).poll_read(cx, &mut self.buf[len..]);
+            let n = match &ret {
+                Poll::Ready(Ok(n)) => *n,
+                _ => 0,
+            };
+            self.buf.truncate(len + n);
+            ret
+        }
+    }
+
+    impl<S> AsyncRead for RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if this.pos < this.buf.len() {
+                let n = buf.len().min(this.buf.len() - this.pos);
+                buf[..n].copy_from_slice(&this.buf[this.pos..this.pos + n]);
+                this.consume_buf(n);
+                return Poll::Ready(Ok(n));
+            }
+            Pin::new(&mut this.inner).poll_read(cx, buf)
+        }
+    }
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+    macro_rules! poll_close {
+        () => {
+            fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+            }
+        };
+    }
+
+    rewind_async_stream!();
+
+    impl<S> RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_fill(&mut self, cx: &mut Context) -> Poll<io::Result<usize>> {
+            let len = self.buf.len();
+            self.buf.resize(len + BUF_SIZE, 0);
+            let mut read_buf = ReadBuf::new(&mut self.buf[len..]);
+            let ret = Pin::new(&mut self.inner).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            self.buf.truncate(len + n);
+            ret.map(|ret| ret.map(|_| n))
+        }
+    }
+
+    impl<S> AsyncRead for RewindAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            if this.pos < this.buf.len() {
+                let n = buf.remaining().min(this.buf.len() - this.pos);
+                buf.put_slice(&this.buf[this.pos..this.pos + n]);
+                this.consume_buf(n);
+                return Poll::Ready(Ok(()));
+            }
+            Pin::new(&mut this.inner).poll_read(cx, buf)
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use futures_x_io::{AsyncRead, AsyncWrite};
+use std::error;
+use std::fmt;
+use std::io;
 
+use crate::io_util::{read_line, try_read, write_all, AsyncIo};
 use crate::tls::TlsClientUpgrader;
-use crate::upgradable::{UpgradableAsyncStream, Upgrader};
+use crate::upgradable::UpgradableAsyncStream;
 
 /*
 SMTP
@@ -28,11 +31,312 @@ pub type SmtpClientInnerStream<S, SU> = UpgradableAsyncStream<S, SU>;
 
 impl<S, SU> SmtpClientInnerStream<S, SU>
 where
-    S: AsyncRead + AsyncWrite + Unpin,
-    SU: TlsClientUpgrader<S> + Unpin,
-    <SU as Upgrader<S>>::Output: AsyncRead + AsyncWrite + Unpin,
+    SU: TlsClientUpgrader<S>,
 {
     pub fn with_smtp_client(stream: S, tls_upgrader: SU) -> Self {
         Self::new(stream, tls_upgrader)
     }
 }
+
+//
+//
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpReply {
+    pub code: u16,
+    // The text of every line, without the code and separator.
+    pub lines: Vec<String>,
+}
+
+impl fmt::Display for SmtpReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code, self.lines.join(" "))
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SmtpError {
+    UnexpectedReply(SmtpReply),
+    InvalidReply,
+    StartTlsNotSupported,
+    DataAfterStartTls,
+}
+
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedReply(reply) => write!(f, "unexpected reply {}", reply),
+            Self::InvalidReply => write!(f, "invalid reply"),
+            Self::StartTlsNotSupported => write!(f, "STARTTLS not supported"),
+            Self::DataAfterStartTls => write!(f, "data after STARTTLS reply"),
+        }
+    }
+}
+
+impl error::Error for SmtpError {}
+
+impl From<SmtpError> for io::Error {
+    fn from(err: SmtpError) -> Self {
+        let kind = match err {
+            SmtpError::InvalidReply | SmtpError::DataAfterStartTls => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+//
+//
+//
+const MAX_LINE_SIZE: usize = 8 * 1024;
+const MAX_REPLY_LINES: usize = 128;
+
+// Multi-line replies are "250-..." lines ended by a "250 ..." one.
+// ref https://tools.ietf.org/html/rfc5321#section-4.2
+async fn read_reply<T>(io: &mut T) -> io::Result<SmtpReply>
+where
+    T: AsyncIo + ?Sized,
+{
+    let mut code = None;
+    let mut lines = vec![];
+    loop {
+        if lines.len() >= MAX_REPLY_LINES {
+            return Err(SmtpError::InvalidReply.into());
+        }
+
+        let line = read_line(io, MAX_LINE_SIZE).await?;
+        let line = String::from_utf8(line).map_err(|_| SmtpError::InvalidReply)?;
+        let line_code = match line.get(..3).and_then(|s| s.parse::<u16>().ok()) {
+            Some(line_code) if (200..600).contains(&line_code) => line_code,
+            _ => return Err(SmtpError::InvalidReply.into()),
+        };
+        if *code.get_or_insert(line_code) != line_code {
+            return Err(SmtpError::InvalidReply.into());
+        }
+
+        let (last, text) = match line.as_bytes().get(3) {
+            None => (true, ""),
+            Some(b' ') => (true, &line[4..]),
+            Some(b'-') => (false, &line[4..]),
+            Some(_) => return Err(SmtpError::InvalidReply.into()),
+        };
+        lines.push(text.to_owned());
+        if last {
+            return Ok(SmtpReply {
+                code: line_code,
+                lines,
+            });
+        }
+    }
+}
+
+async fn expect_reply<T>(io: &mut T, code: u16) -> io::Result<SmtpReply>
+where
+    T: AsyncIo + ?Sized,
+{
+    let reply = read_reply(io).await?;
+    if reply.code != code {
+        return Err(SmtpError::UnexpectedReply(reply).into());
+    }
+    Ok(reply)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SmtpStartTlsClient {
+    pub(crate) domain: String,
+}
+
+impl SmtpStartTlsClient {
+    // Returns the extensions, the first line of the EHLO reply is the server's greeting.
+    pub(crate) async fn ehlo<T>(&self, io: &mut T) -> io::Result<Vec<String>>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        if self
+            .domain
+            .bytes()
+            .any(|b| matches!(b, b'\r' | b'\n' | b'\0'))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "CR, LF or NUL in the EHLO domain",
+            ));
+        }
+        write_all(io, format!("EHLO {}\r\n", self.domain).as_bytes()).await?;
+        let reply = expect_reply(io, 250).await?;
+        Ok(reply.lines.into_iter().skip(1).collect())
+    }
+
+    // Everything up to the TLS handshake.
+    pub(crate) async fn start_tls<T>(&self, io: &mut T) -> io::Result<()>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        expect_reply(io, 220).await?;
+
+        let extensions = self.ehlo(io).await?;
+        let supported = extensions.iter().any(|ext| {
+            ext.split(' ')
+                .next()
+                .map(|keyword| keyword.eq_ignore_ascii_case("STARTTLS"))
+                .unwrap_or(false)
+        });
+        if !supported {
+            return Err(SmtpError::StartTlsNotSupported.into());
+        }
+
+        write_all(io, b"STARTTLS\r\n").await?;
+        expect_reply(io, 220).await?;
+
+        // The server must wait for the handshake, anything already sent could be injected.
+        let mut byte = [0; 1];
+        if matches!(try_read(io, &mut byte).await?, Some(n) if n > 0) {
+            return Err(SmtpError::DataAfterStartTls.into());
+        }
+        Ok(())
+    }
+}
+
+//
+//
+//
+macro_rules! smtp_start_tls_upgrader {
+    () => {
+        use std::io;
+
+        use async_trait::async_trait;
+
+        use super::SmtpStartTlsClient;
+        use crate::tls::TlsClientUpgrader;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        /*
+        Runs the Case1 flow, the stream is ready for AUTH afterwards.
+        */
+        #[derive(Debug, Clone)]
+        pub struct SmtpStartTlsUpgrader<TU> {
+            client: SmtpStartTlsClient,
+            tls_upgrader: TU,
+            capabilities: Option<Vec<String>>,
+        }
+
+        impl<TU> SmtpStartTlsUpgrader<TU> {
+            pub fn new(tls_upgrader: TU) -> Self {
+                Self {
+                    client: SmtpStartTlsClient {
+                        domain: "localhost".to_owned(),
+                    },
+                    tls_upgrader,
+                    capabilities: None,
+                }
+            }
+
+            // Sent with EHLO, localhost by default. CR, LF or NUL in it fails the upgrade with
+            // InvalidInput.
+            pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+                self.client.domain = domain.into();
+                self
+            }
+
+            // The EHLO extensions sent over TLS, set by the upgrade. Through the stream, see
+            // UpgradableAsyncStream::upgrader.
+            pub fn capabilities(&self) -> Option<&[String]> {
+                self.capabilities.as_deref()
+            }
+
+            pub fn get_ref(&self) -> &TU {
+                &self.tls_upgrader
+            }
+
+            pub fn get_mut(&mut self) -> &mut TU {
+                &mut self.tls_upgrader
+            }
+
+            pub fn into_inner(self) -> TU {
+                self.tls_upgrader
+            }
+        }
+
+        #[async_trait]
+        impl<S, TU> Upgrader<S> for SmtpStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + Send,
+            TU::Output: AsyncRead + AsyncWrite + Unpin + Send,
+        {
+            type Output = TU::Output;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                self.capabilities = None;
+
+                self.client.start_tls(&mut Compat(&mut stream)).await?;
+                let mut stream = self.tls_upgrader.upgrade(stream).await?;
+                // What was advertised before TLS can't be trusted.
+                let capabilities = self.client.ehlo(&mut Compat(&mut stream)).await?;
+                self.capabilities = Some(capabilities);
+                Ok(stream)
+            }
+        }
+
+        impl<S, TU> TlsClientUpgrader<S> for SmtpStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + Send,
+            TU::Output: AsyncRead + AsyncWrite + Unpin + Send,
+        {
+        }
+
+        impl<S, TU> UpgraderExtRefer<S> for SmtpStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + UpgraderExtRefer<S> + Send,
+            TU::Output: AsyncRead + AsyncWrite + Unpin + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                TU::get_ref(output)
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                TU::get_mut(output)
+            }
+        }
+
+        impl<S, TU> UpgraderExtIntoStream<S> for SmtpStartTlsUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsClientUpgrader<S> + UpgraderExtIntoStream<S> + Send,
+            TU::Output: AsyncRead + AsyncWrite + Unpin + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                TU::into_stream(output)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    smtp_start_tls_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    smtp_start_tls_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    smtp_start_tls_upgrader!();
+}
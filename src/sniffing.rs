@@ -0,0 +1,141 @@
+// A TLS handshake record of version 3.x carrying a ClientHello.
+fn is_client_hello(buf: &[u8]) -> bool {
+    buf.len() >= 6 && buf[0] == 0x16 && buf[1] == 0x03 && buf[5] == 0x01
+}
+
+macro_rules! sniffing_server_upgrader {
+    () => {
+        use std::io;
+
+        use async_trait::async_trait;
+        use either::Either;
+
+        use super::is_client_hello;
+        use crate::tls::TlsServerUpgrader;
+        use crate::unionable::UnionableAsyncStream;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        /*
+        Serves implicit TLS and plaintext on the same port. The first bytes the client sends
+        decide, they are replayed to whichever side handles the connection. Protocols where the
+        server speaks first can't be told apart this way.
+
+        Both sides get the stream as a RewindAsyncStream<S> rather than S, it holds the bytes to
+        replay, so the output is UnionableAsyncStream<RewindAsyncStream<S>, TU::Output> and TU
+        upgrades RewindAsyncStream<S>. UpgraderExtRefer and UpgraderExtIntoStream still reach S.
+        */
+        #[derive(Debug, Clone)]
+        pub struct SniffingServerUpgrader<TU> {
+            tls_upgrader: TU,
+        }
+
+        impl<TU> SniffingServerUpgrader<TU> {
+            pub fn new(tls_upgrader: TU) -> Self {
+                Self { tls_upgrader }
+            }
+
+            pub fn get_ref(&self) -> &TU {
+                &self.tls_upgrader
+            }
+
+            pub fn get_mut(&mut self) -> &mut TU {
+                &mut self.tls_upgrader
+            }
+
+            pub fn into_inner(self) -> TU {
+                self.tls_upgrader
+            }
+        }
+
+        #[async_trait]
+        impl<S, TU> Upgrader<S> for SniffingServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<RewindAsyncStream<S>> + Send,
+        {
+            type Output = UnionableAsyncStream<RewindAsyncStream<S>, TU::Output>;
+            async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+                let mut stream = RewindAsyncStream::new(stream);
+
+                // Plaintext clients may send a single short line and wait, so don't ask for more
+                // than one byte unless it looks like a handshake record. Nothing served in
+                // plaintext starts with 0x16, so past that it is safe to wait for the header.
+                let is_tls =
+                    stream.peek(1).await? == [0x16] && is_client_hello(stream.peek(6).await?);
+
+                if is_tls {
+                    self.tls_upgrader
+                        .upgrade(stream)
+                        .await
+                        .map(UnionableAsyncStream::the_other)
+                } else {
+                    Ok(UnionableAsyncStream::one(stream))
+                }
+            }
+        }
+
+        impl<S, TU> UpgraderExtRefer<S> for SniffingServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<RewindAsyncStream<S>>
+                + UpgraderExtRefer<RewindAsyncStream<S>>
+                + Send,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                match output.get_ref() {
+                    Either::Left(stream) => stream.get_ref(),
+                    Either::Right(output) => TU::get_ref(output).get_ref(),
+                }
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                match output.get_mut() {
+                    Either::Left(stream) => stream.get_mut(),
+                    Either::Right(output) => TU::get_mut(output).get_mut(),
+                }
+            }
+        }
+
+        impl<S, TU> UpgraderExtIntoStream<S> for SniffingServerUpgrader<TU>
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+            TU: TlsServerUpgrader<RewindAsyncStream<S>>
+                + UpgraderExtIntoStream<RewindAsyncStream<S>>
+                + Send,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                match output.into_inner() {
+                    Either::Left(stream) => stream.into_inner(),
+                    Either::Right(output) => TU::into_stream(output)?.into_inner(),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::rewind::futures_io::RewindAsyncStream;
+
+    sniffing_server_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::rewind::tokio_io::RewindAsyncStream;
+
+    sniffing_server_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::rewind::tokio1_io::RewindAsyncStream;
+
+    sniffing_server_upgrader!();
+}
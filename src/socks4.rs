@@ -0,0 +1,246 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use crate::io_util::{read_exact, write_all, AsyncIo};
+use crate::socks5::TargetAddr;
+use crate::upgradable::Upgrader;
+
+// ref https://www.openssh.com/txt/socks4.protocol and https://www.openssh.com/txt/socks4a.protocol
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Socks4Error {
+    Rejected,
+    IdentdUnreachable,
+    IdentdMismatch,
+    UnknownReply(u8),
+    InvalidResponse,
+    Ipv6NotSupported,
+    NulInField,
+}
+
+impl Socks4Error {
+    fn from_reply(cd: u8) -> Self {
+        match cd {
+            0x5B => Self::Rejected,
+            0x5C => Self::IdentdUnreachable,
+            0x5D => Self::IdentdMismatch,
+            cd => Self::UnknownReply(cd),
+        }
+    }
+}
+
+impl fmt::Display for Socks4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected => write!(f, "request rejected or failed"),
+            Self::IdentdUnreachable => write!(f, "identd unreachable"),
+            Self::IdentdMismatch => write!(f, "identd user-id mismatch"),
+            Self::UnknownReply(cd) => write!(f, "unknown reply {}", cd),
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::Ipv6NotSupported => write!(f, "ipv6 not supported"),
+            Self::NulInField => write!(f, "NUL in user-id or hostname"),
+        }
+    }
+}
+
+impl error::Error for Socks4Error {}
+
+impl From<Socks4Error> for io::Error {
+    fn from(err: Socks4Error) -> Self {
+        let kind = match err {
+            Socks4Error::Rejected => io::ErrorKind::ConnectionRefused,
+            Socks4Error::IdentdUnreachable | Socks4Error::IdentdMismatch => {
+                io::ErrorKind::PermissionDenied
+            }
+            Socks4Error::InvalidResponse => io::ErrorKind::InvalidData,
+            Socks4Error::Ipv6NotSupported | Socks4Error::NulInField => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+//
+//
+//
+const VERSION: u8 = 0x04;
+
+const CMD_CONNECT: u8 = 0x01;
+
+const REPLY_VERSION: u8 = 0x00;
+const REPLY_GRANTED: u8 = 0x5A;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Socks4Client {
+    pub(crate) target: TargetAddr,
+    pub(crate) user_id: String,
+}
+
+impl Socks4Client {
+    // Returns the address the proxy bound for the connection.
+    pub(crate) async fn handshake<T>(&self, io: &mut T) -> io::Result<SocketAddr>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        // Both are NUL-terminated, an embedded NUL would end the field early.
+        if self.user_id.contains('\0') {
+            return Err(Socks4Error::NulInField.into());
+        }
+
+        let mut buf = vec![VERSION, CMD_CONNECT];
+        match &self.target {
+            TargetAddr::Ip(SocketAddr::V4(addr)) => {
+                buf.extend_from_slice(&addr.port().to_be_bytes());
+                buf.extend_from_slice(&addr.ip().octets());
+                buf.extend_from_slice(self.user_id.as_bytes());
+                buf.push(0x00);
+            }
+            TargetAddr::Ip(SocketAddr::V6(_)) => {
+                return Err(Socks4Error::Ipv6NotSupported.into());
+            }
+            TargetAddr::Domain(host, _) if host.contains('\0') => {
+                return Err(Socks4Error::NulInField.into());
+            }
+            TargetAddr::Domain(host, port) => {
+                // SOCKS4a, 0.0.0.x tells the proxy that the hostname follows the user-id.
+                buf.extend_from_slice(&port.to_be_bytes());
+                buf.extend_from_slice(&[0, 0, 0, 1]);
+                buf.extend_from_slice(self.user_id.as_bytes());
+                buf.push(0x00);
+                buf.extend_from_slice(host.as_bytes());
+                buf.push(0x00);
+            }
+        }
+        write_all(io, &buf).await?;
+
+        let mut buf = [0; 8];
+        read_exact(io, &mut buf).await?;
+        if buf[0] != REPLY_VERSION {
+            return Err(Socks4Error::InvalidResponse.into());
+        }
+        if buf[1] != REPLY_GRANTED {
+            return Err(Socks4Error::from_reply(buf[1]).into());
+        }
+
+        let port = u16::from_be_bytes([buf[2], buf[3]]);
+        let ip = Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]);
+        Ok(SocketAddrV4::new(ip, port).into())
+    }
+}
+
+// Speaks SOCKS4 (or SOCKS4a) to the proxy, the stream then carries the target's bytes.
+pub trait Socks4ProxyUpgrader<S>: Upgrader<S, Output = S> {}
+
+macro_rules! socks4_client_upgrader {
+    () => {
+        use std::io;
+        use std::net::SocketAddr;
+
+        use async_trait::async_trait;
+
+        use super::{Socks4Client, Socks4ProxyUpgrader};
+        use crate::socks5::TargetAddr;
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        #[derive(Debug, Clone)]
+        pub struct Socks4ClientUpgrader {
+            client: Socks4Client,
+            bound_addr: Option<SocketAddr>,
+        }
+
+        impl Socks4ClientUpgrader {
+            pub fn new(target: impl Into<TargetAddr>) -> Self {
+                Self {
+                    client: Socks4Client {
+                        target: target.into(),
+                        user_id: String::new(),
+                    },
+                    bound_addr: None,
+                }
+            }
+
+            pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+                self.client.user_id = user_id.into();
+                self
+            }
+
+            pub fn target(&self) -> &TargetAddr {
+                &self.client.target
+            }
+
+            pub fn bound_addr(&self) -> Option<SocketAddr> {
+                self.bound_addr
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for Socks4ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            type Output = S;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                let bound_addr = self.client.handshake(&mut Compat(&mut stream)).await?;
+                self.bound_addr = Some(bound_addr);
+                Ok(stream)
+            }
+        }
+
+        impl<S> UpgraderExtRefer<S> for Socks4ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output
+            }
+        }
+
+        impl<S> UpgraderExtIntoStream<S> for Socks4ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+
+        impl<S> Socks4ProxyUpgrader<S> for Socks4ClientUpgrader where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+        {
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    socks4_client_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    socks4_client_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    socks4_client_upgrader!();
+}
@@ -0,0 +1,384 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+use crate::io_util::{read_exact, write_all, AsyncIo};
+use crate::upgradable::Upgrader;
+
+// ref https://tools.ietf.org/html/rfc1928 and https://tools.ietf.org/html/rfc1929
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetAddr {
+    Ip(SocketAddr),
+    // Resolved by the proxy.
+    Domain(String, u16),
+}
+
+impl From<SocketAddr> for TargetAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Ip(addr)
+    }
+}
+
+impl From<(String, u16)> for TargetAddr {
+    fn from((host, port): (String, u16)) -> Self {
+        Self::Domain(host, port)
+    }
+}
+
+impl From<(&str, u16)> for TargetAddr {
+    fn from((host, port): (&str, u16)) -> Self {
+        Self::Domain(host.to_owned(), port)
+    }
+}
+
+impl fmt::Display for TargetAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(addr) => write!(f, "{}", addr),
+            Self::Domain(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+//
+//
+//
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Socks5Error {
+    NoAcceptableMethods,
+    AuthFailed,
+    GeneralFailure,
+    NotAllowed,
+    NetworkUnreachable,
+    HostUnreachable,
+    ConnectionRefused,
+    TtlExpired,
+    CommandNotSupported,
+    AddressTypeNotSupported,
+    UnknownReply(u8),
+    InvalidResponse,
+    DomainTooLong,
+    CredentialsTooLong,
+}
+
+impl Socks5Error {
+    fn from_reply(rep: u8) -> Self {
+        match rep {
+            0x01 => Self::GeneralFailure,
+            0x02 => Self::NotAllowed,
+            0x03 => Self::NetworkUnreachable,
+            0x04 => Self::HostUnreachable,
+            0x05 => Self::ConnectionRefused,
+            0x06 => Self::TtlExpired,
+            0x07 => Self::CommandNotSupported,
+            0x08 => Self::AddressTypeNotSupported,
+            rep => Self::UnknownReply(rep),
+        }
+    }
+}
+
+impl fmt::Display for Socks5Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAcceptableMethods => write!(f, "no acceptable methods"),
+            Self::AuthFailed => write!(f, "authentication failed"),
+            Self::GeneralFailure => write!(f, "general SOCKS server failure"),
+            Self::NotAllowed => write!(f, "connection not allowed by ruleset"),
+            Self::NetworkUnreachable => write!(f, "network unreachable"),
+            Self::HostUnreachable => write!(f, "host unreachable"),
+            Self::ConnectionRefused => write!(f, "connection refused"),
+            Self::TtlExpired => write!(f, "TTL expired"),
+            Self::CommandNotSupported => write!(f, "command not supported"),
+            Self::AddressTypeNotSupported => write!(f, "address type not supported"),
+            Self::UnknownReply(rep) => write!(f, "unknown reply {}", rep),
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::DomainTooLong => write!(f, "domain too long"),
+            Self::CredentialsTooLong => write!(f, "username or password too long"),
+        }
+    }
+}
+
+impl error::Error for Socks5Error {}
+
+impl From<Socks5Error> for io::Error {
+    fn from(err: Socks5Error) -> Self {
+        let kind = match err {
+            Socks5Error::AuthFailed | Socks5Error::NotAllowed => io::ErrorKind::PermissionDenied,
+            Socks5Error::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            Socks5Error::InvalidResponse => io::ErrorKind::InvalidData,
+            Socks5Error::DomainTooLong | Socks5Error::CredentialsTooLong => {
+                io::ErrorKind::InvalidInput
+            }
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+//
+//
+//
+const VERSION: u8 = 0x05;
+
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xFF;
+
+const CMD_CONNECT: u8 = 0x01;
+
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Socks5Client {
+    pub(crate) target: TargetAddr,
+    pub(crate) auth: Option<(String, String)>,
+}
+
+impl Socks5Client {
+    // Returns the address the proxy bound for the connection.
+    pub(crate) async fn handshake<T>(&self, io: &mut T) -> io::Result<TargetAddr>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        let method = if self.auth.is_some() {
+            write_all(io, &[VERSION, 2, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD]).await?;
+            self.read_method(io).await?
+        } else {
+            write_all(io, &[VERSION, 1, METHOD_NO_AUTH]).await?;
+            self.read_method(io).await?
+        };
+
+        match (method, &self.auth) {
+            (METHOD_NO_AUTH, _) => {}
+            (METHOD_USERNAME_PASSWORD, Some((username, password))) => {
+                self.authenticate(io, username, password).await?
+            }
+            (METHOD_NO_ACCEPTABLE, _) => return Err(Socks5Error::NoAcceptableMethods.into()),
+            _ => return Err(Socks5Error::InvalidResponse.into()),
+        }
+
+        let mut buf = vec![VERSION, CMD_CONNECT, 0x00];
+        match &self.target {
+            TargetAddr::Ip(SocketAddr::V4(addr)) => {
+                buf.push(ATYP_IPV4);
+                buf.extend_from_slice(&addr.ip().octets());
+                buf.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            TargetAddr::Ip(SocketAddr::V6(addr)) => {
+                buf.push(ATYP_IPV6);
+                buf.extend_from_slice(&addr.ip().octets());
+                buf.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            TargetAddr::Domain(host, port) => {
+                if host.len() > u8::MAX as usize {
+                    return Err(Socks5Error::DomainTooLong.into());
+                }
+                buf.push(ATYP_DOMAIN);
+                buf.push(host.len() as u8);
+                buf.extend_from_slice(host.as_bytes());
+                buf.extend_from_slice(&port.to_be_bytes());
+            }
+        }
+        write_all(io, &buf).await?;
+
+        let mut head = [0; 4];
+        read_exact(io, &mut head).await?;
+        if head[0] != VERSION {
+            return Err(Socks5Error::InvalidResponse.into());
+        }
+        if head[1] != 0x00 {
+            return Err(Socks5Error::from_reply(head[1]).into());
+        }
+
+        match head[3] {
+            ATYP_IPV4 => {
+                let mut buf = [0; 6];
+                read_exact(io, &mut buf).await?;
+                let ip = Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3]);
+                let port = u16::from_be_bytes([buf[4], buf[5]]);
+                Ok(SocketAddr::from(SocketAddrV4::new(ip, port)).into())
+            }
+            ATYP_IPV6 => {
+                let mut buf = [0; 18];
+                read_exact(io, &mut buf).await?;
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&buf[..16]);
+                let port = u16::from_be_bytes([buf[16], buf[17]]);
+                Ok(SocketAddr::from(SocketAddrV6::new(Ipv6Addr::from(octets), port, 0, 0)).into())
+            }
+            ATYP_DOMAIN => {
+                let mut len = [0; 1];
+                read_exact(io, &mut len).await?;
+                let mut buf = vec![0; len[0] as usize + 2];
+                read_exact(io, &mut buf).await?;
+                let port = u16::from_be_bytes([buf[buf.len() - 2], buf[buf.len() - 1]]);
+                buf.truncate(buf.len() - 2);
+                let host = String::from_utf8(buf).map_err(|_| Socks5Error::InvalidResponse)?;
+                Ok(TargetAddr::Domain(host, port))
+            }
+            _ => Err(Socks5Error::InvalidResponse.into()),
+        }
+    }
+
+    async fn read_method<T>(&self, io: &mut T) -> io::Result<u8>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        let mut buf = [0; 2];
+        read_exact(io, &mut buf).await?;
+        if buf[0] != VERSION {
+            return Err(Socks5Error::InvalidResponse.into());
+        }
+        Ok(buf[1])
+    }
+
+    async fn authenticate<T>(&self, io: &mut T, username: &str, password: &str) -> io::Result<()>
+    where
+        T: AsyncIo + ?Sized,
+    {
+        if username.len() > u8::MAX as usize || password.len() > u8::MAX as usize {
+            return Err(Socks5Error::CredentialsTooLong.into());
+        }
+
+        let mut buf = vec![0x01, username.len() as u8];
+        buf.extend_from_slice(username.as_bytes());
+        buf.push(password.len() as u8);
+        buf.extend_from_slice(password.as_bytes());
+        write_all(io, &buf).await?;
+
+        let mut buf = [0; 2];
+        read_exact(io, &mut buf).await?;
+        if buf[0] != 0x01 {
+            return Err(Socks5Error::InvalidResponse.into());
+        }
+        if buf[1] != 0x00 {
+            return Err(Socks5Error::AuthFailed.into());
+        }
+        Ok(())
+    }
+}
+
+// Speaks SOCKS5 to the proxy, the stream then carries the target's bytes.
+pub trait Socks5ProxyUpgrader<S>: Upgrader<S, Output = S> {}
+
+/*
+Like the syncable streams, the upgrader needs the io traits of S, so every io flavor gets its own
+Socks5ClientUpgrader.
+*/
+macro_rules! socks5_client_upgrader {
+    () => {
+        use std::io;
+
+        use async_trait::async_trait;
+
+        use super::{Socks5Client, Socks5ProxyUpgrader, TargetAddr};
+        use crate::upgradable::Upgrader;
+        use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+        #[derive(Debug, Clone)]
+        pub struct Socks5ClientUpgrader {
+            client: Socks5Client,
+            bound_addr: Option<TargetAddr>,
+        }
+
+        impl Socks5ClientUpgrader {
+            pub fn new(target: impl Into<TargetAddr>) -> Self {
+                Self {
+                    client: Socks5Client {
+                        target: target.into(),
+                        auth: None,
+                    },
+                    bound_addr: None,
+                }
+            }
+
+            pub fn with_auth(
+                mut self,
+                username: impl Into<String>,
+                password: impl Into<String>,
+            ) -> Self {
+                self.client.auth = Some((username.into(), password.into()));
+                self
+            }
+
+            pub fn target(&self) -> &TargetAddr {
+                &self.client.target
+            }
+
+            pub fn bound_addr(&self) -> Option<&TargetAddr> {
+                self.bound_addr.as_ref()
+            }
+        }
+
+        #[async_trait]
+        impl<S> Upgrader<S> for Socks5ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            type Output = S;
+            async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+                let bound_addr = self.client.handshake(&mut Compat(&mut stream)).await?;
+                self.bound_addr = Some(bound_addr);
+                Ok(stream)
+            }
+        }
+
+        impl<S> UpgraderExtRefer<S> for Socks5ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn get_ref(output: &Self::Output) -> &S {
+                output
+            }
+            fn get_mut(output: &mut Self::Output) -> &mut S {
+                output
+            }
+        }
+
+        impl<S> UpgraderExtIntoStream<S> for Socks5ClientUpgrader
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        {
+            fn into_stream(output: Self::Output) -> io::Result<S> {
+                Ok(output)
+            }
+        }
+
+        impl<S> Socks5ProxyUpgrader<S> for Socks5ClientUpgrader where
+            S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+        {
+        }
+    };
+}
+
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use ::futures_io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::futures_io::Compat;
+
+    socks5_client_upgrader!();
+}
+
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio_io::Compat;
+
+    socks5_client_upgrader!();
+}
+
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use tokio1::io::{AsyncRead, AsyncWrite};
+
+    use crate::io_util::tokio1_io::Compat;
+
+    socks5_client_upgrader!();
+}
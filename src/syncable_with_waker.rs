@@ -1,24 +1,18 @@
 /*
 Ref https://github.com/sdroege/async-tungstenite/blob/0.7.1/src/compat.rs
+
+std::io::{Read, Write, ...} can only be implemented once per type, so every io flavor gets its own
+SyncableWithWakerAsyncStream.
 */
 
-use std::io::{self, Read, Seek, Write};
-use std::pin::Pin;
 use std::sync::Arc;
 
 use futures_core::task::__internal::AtomicWaker;
-use futures_task::{waker_ref, ArcWake, Context, Poll, Waker};
-use futures_x_io::{AsyncRead, AsyncSeek, AsyncWrite};
-
-pub struct SyncableWithWakerAsyncStream<S> {
-    inner: S,
-    read_waker: Arc<WakerInner>,
-    write_waker: Arc<WakerInner>,
-}
+use futures_task::ArcWake;
 
 #[derive(Default)]
-struct WakerInner {
-    waker: AtomicWaker,
+pub(crate) struct WakerInner {
+    pub(crate) waker: AtomicWaker,
 }
 
 impl ArcWake for WakerInner {
@@ -32,117 +26,367 @@ pub enum WakerKind {
     Write,
 }
 
-impl<S> SyncableWithWakerAsyncStream<S> {
-    pub fn new(inner: S, waker: &Waker) -> Self {
-        let this = Self {
-            inner,
-            read_waker: Default::default(),
-            write_waker: Default::default(),
-        };
+#[cfg(feature = "futures_io")]
+pub mod futures_io {
+    use std::io::{self, Read, Seek, Write};
+    use std::pin::Pin;
+    use std::sync::Arc;
 
-        this.read_waker.waker.register(waker);
-        this.write_waker.waker.register(waker);
+    use ::futures_io::{AsyncRead, AsyncSeek, AsyncWrite};
+    use futures_task::{waker_ref, Context, Poll, Waker};
 
-        this
-    }
+    use super::{WakerInner, WakerKind};
 
-    pub fn get_ref(&self) -> &S {
-        &self.inner
+    pub struct SyncableWithWakerAsyncStream<S> {
+        inner: S,
+        read_waker: Arc<WakerInner>,
+        write_waker: Arc<WakerInner>,
     }
 
-    pub fn get_mut(&mut self) -> &mut S {
-        &mut self.inner
+    impl<S> SyncableWithWakerAsyncStream<S> {
+        pub fn new(inner: S, waker: &Waker) -> Self {
+            let this = Self {
+                inner,
+                read_waker: Default::default(),
+                write_waker: Default::default(),
+            };
+
+            this.read_waker.waker.register(waker);
+            this.write_waker.waker.register(waker);
+
+            this
+        }
+
+        pub fn get_ref(&self) -> &S {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut S {
+            &mut self.inner
+        }
+
+        pub fn into_inner(self) -> S {
+            self.inner
+        }
+
+        pub fn set_waker(&self, waker: &Waker) {
+            self.read_waker.waker.register(waker);
+            self.write_waker.waker.register(waker);
+        }
+
+        pub fn set_waker_with_kind(&self, waker: &Waker, kind: WakerKind) {
+            match kind {
+                WakerKind::Read => self.read_waker.waker.register(waker),
+                WakerKind::Write => self.write_waker.waker.register(waker),
+            }
+        }
     }
 
-    pub fn into_inner(self) -> S {
-        self.inner
+    impl<S> SyncableWithWakerAsyncStream<S>
+    where
+        S: Unpin,
+    {
+        fn with_context<F, T>(&mut self, kind: WakerKind, f: F) -> Poll<io::Result<T>>
+        where
+            F: FnOnce(&mut Context, Pin<&mut S>) -> Poll<io::Result<T>>,
+        {
+            let waker = match kind {
+                WakerKind::Read => waker_ref(&self.read_waker),
+                WakerKind::Write => waker_ref(&self.write_waker),
+            };
+            let mut context = Context::from_waker(&waker);
+            f(&mut context, Pin::new(&mut self.inner))
+        }
     }
 
-    pub fn set_waker(&self, waker: &Waker) {
-        self.read_waker.waker.register(waker);
-        self.write_waker.waker.register(waker);
+    impl<S> Write for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_write(cx, buf)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_flush(cx)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
     }
 
-    pub fn set_waker_with_kind(&self, waker: &Waker, kind: WakerKind) {
-        match kind {
-            WakerKind::Read => self.read_waker.waker.register(waker),
-            WakerKind::Write => self.write_waker.waker.register(waker),
+    impl<S> Read for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.with_context(WakerKind::Read, |cx, stream| stream.poll_read(cx, buf)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
         }
     }
-}
 
-impl<S> SyncableWithWakerAsyncStream<S>
-where
-    S: Unpin,
-{
-    fn with_context<F, T>(&mut self, kind: WakerKind, f: F) -> Poll<io::Result<T>>
+    impl<S> Seek for SyncableWithWakerAsyncStream<S>
     where
-        F: FnOnce(&mut Context, Pin<&mut S>) -> Poll<io::Result<T>>,
+        S: AsyncSeek + Unpin,
     {
-        let waker = match kind {
-            WakerKind::Read => waker_ref(&self.read_waker),
-            WakerKind::Write => waker_ref(&self.write_waker),
-        };
-        let mut context = Context::from_waker(&waker);
-        f(&mut context, Pin::new(&mut self.inner))
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            match self.with_context(WakerKind::Read, |cx, stream| stream.poll_seek(cx, pos)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
     }
+
+    // lifetime problem with BufRead
 }
 
-impl<S> Write for SyncableWithWakerAsyncStream<S>
-where
-    S: AsyncWrite + Unpin,
-{
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self.with_context(WakerKind::Write, |cx, stream| stream.poll_write(cx, buf)) {
-            Poll::Ready(ret) => ret,
-            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+#[cfg(feature = "tokio_io")]
+pub mod tokio_io {
+    use std::io::{self, Read, Seek, Write};
+    use std::pin::Pin;
+    use std::sync::Arc;
+
+    use futures_task::{waker_ref, Context, Poll, Waker};
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+    use super::{WakerInner, WakerKind};
+
+    pub struct SyncableWithWakerAsyncStream<S> {
+        inner: S,
+        read_waker: Arc<WakerInner>,
+        write_waker: Arc<WakerInner>,
+    }
+
+    impl<S> SyncableWithWakerAsyncStream<S> {
+        pub fn new(inner: S, waker: &Waker) -> Self {
+            let this = Self {
+                inner,
+                read_waker: Default::default(),
+                write_waker: Default::default(),
+            };
+
+            this.read_waker.waker.register(waker);
+            this.write_waker.waker.register(waker);
+
+            this
+        }
+
+        pub fn get_ref(&self) -> &S {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut S {
+            &mut self.inner
+        }
+
+        pub fn into_inner(self) -> S {
+            self.inner
+        }
+
+        pub fn set_waker(&self, waker: &Waker) {
+            self.read_waker.waker.register(waker);
+            self.write_waker.waker.register(waker);
+        }
+
+        pub fn set_waker_with_kind(&self, waker: &Waker, kind: WakerKind) {
+            match kind {
+                WakerKind::Read => self.read_waker.waker.register(waker),
+                WakerKind::Write => self.write_waker.waker.register(waker),
+            }
         }
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        match self.with_context(WakerKind::Write, |cx, stream| stream.poll_flush(cx)) {
-            Poll::Ready(ret) => ret,
-            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    impl<S> SyncableWithWakerAsyncStream<S>
+    where
+        S: Unpin,
+    {
+        fn with_context<F, T>(&mut self, kind: WakerKind, f: F) -> Poll<io::Result<T>>
+        where
+            F: FnOnce(&mut Context, Pin<&mut S>) -> Poll<io::Result<T>>,
+        {
+            let waker = match kind {
+                WakerKind::Read => waker_ref(&self.read_waker),
+                WakerKind::Write => waker_ref(&self.write_waker),
+            };
+            let mut context = Context::from_waker(&waker);
+            f(&mut context, Pin::new(&mut self.inner))
         }
     }
-}
 
-impl<S> Read for SyncableWithWakerAsyncStream<S>
-where
-    S: AsyncRead + Unpin,
-{
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        match self.with_context(WakerKind::Read, |cx, stream| stream.poll_read(cx, buf)) {
-            Poll::Ready(ret) => ret,
-            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    impl<S> Write for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_write(cx, buf)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_flush(cx)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
         }
     }
-}
 
-impl<S> Seek for SyncableWithWakerAsyncStream<S>
-where
-    S: AsyncSeek + Unpin,
-{
-    #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
-        match self.with_context(WakerKind::Read, |cx, stream| stream.poll_seek(cx, pos)) {
-            Poll::Ready(ret) => ret,
-            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
-        }
-    }
-
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
-        match self.with_context(WakerKind::Read, |cx, stream| stream.start_seek(cx, pos)) {
-            Poll::Ready(_) => {
-                match self.with_context(WakerKind::Read, |cx, stream| stream.poll_complete(cx)) {
-                    Poll::Ready(ret) => ret,
-                    Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    impl<S> Read for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.with_context(WakerKind::Read, |cx, stream| stream.poll_read(cx, buf)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl<S> Seek for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncSeek + Unpin,
+    {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            match self.with_context(WakerKind::Read, |cx, stream| stream.start_seek(cx, pos)) {
+                Poll::Ready(_) => {
+                    match self.with_context(WakerKind::Read, |cx, stream| stream.poll_complete(cx))
+                    {
+                        Poll::Ready(ret) => ret,
+                        Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+                    }
                 }
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
             }
-            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
         }
     }
+
+    // lifetime problem with BufRead
 }
 
-// lifetime problem with BufRead
+#[cfg(feature = "tokio1_io")]
+pub mod tokio1_io {
+    use std::io::{self, Read, Seek, Write};
+    use std::pin::Pin;
+    use std::sync::Arc;
+
+    use futures_task::{waker_ref, Context, Poll, Waker};
+    use tokio1::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    use super::{WakerInner, WakerKind};
+
+    pub struct SyncableWithWakerAsyncStream<S> {
+        inner: S,
+        read_waker: Arc<WakerInner>,
+        write_waker: Arc<WakerInner>,
+    }
+
+    impl<S> SyncableWithWakerAsyncStream<S> {
+        pub fn new(inner: S, waker: &Waker) -> Self {
+            let this = Self {
+                inner,
+                read_waker: Default::default(),
+                write_waker: Default::default(),
+            };
+
+            this.read_waker.waker.register(waker);
+            this.write_waker.waker.register(waker);
+
+            this
+        }
+
+        pub fn get_ref(&self) -> &S {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut S {
+            &mut self.inner
+        }
+
+        pub fn into_inner(self) -> S {
+            self.inner
+        }
+
+        pub fn set_waker(&self, waker: &Waker) {
+            self.read_waker.waker.register(waker);
+            self.write_waker.waker.register(waker);
+        }
+
+        pub fn set_waker_with_kind(&self, waker: &Waker, kind: WakerKind) {
+            match kind {
+                WakerKind::Read => self.read_waker.waker.register(waker),
+                WakerKind::Write => self.write_waker.waker.register(waker),
+            }
+        }
+    }
+
+    impl<S> SyncableWithWakerAsyncStream<S>
+    where
+        S: Unpin,
+    {
+        fn with_context<F, T>(&mut self, kind: WakerKind, f: F) -> Poll<io::Result<T>>
+        where
+            F: FnOnce(&mut Context, Pin<&mut S>) -> Poll<io::Result<T>>,
+        {
+            let waker = match kind {
+                WakerKind::Read => waker_ref(&self.read_waker),
+                WakerKind::Write => waker_ref(&self.write_waker),
+            };
+            let mut context = Context::from_waker(&waker);
+            f(&mut context, Pin::new(&mut self.inner))
+        }
+    }
+
+    impl<S> Write for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_write(cx, buf)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match self.with_context(WakerKind::Write, |cx, stream| stream.poll_flush(cx)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl<S> Read for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncRead + Unpin,
+    {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut buf = ReadBuf::new(buf);
+            match self.with_context(WakerKind::Read, |cx, stream| stream.poll_read(cx, &mut buf)) {
+                Poll::Ready(ret) => ret.map(|_| buf.filled().len()),
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl<S> Seek for SyncableWithWakerAsyncStream<S>
+    where
+        S: AsyncSeek + Unpin,
+    {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            Pin::new(&mut self.inner).start_seek(pos)?;
+            match self.with_context(WakerKind::Read, |cx, stream| stream.poll_complete(cx)) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    // lifetime problem with BufRead
+}
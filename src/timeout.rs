@@ -0,0 +1,252 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::gradable::{Downgrader, GradableAsyncStream};
+use crate::socks4::Socks4ProxyUpgrader;
+use crate::socks5::Socks5ProxyUpgrader;
+use crate::tls::{TlsClientUpgrader, TlsServerUpgrader};
+use crate::upgradable::{UpgradableAsyncStream, Upgrader};
+use crate::upgradable_error::UpgradeError;
+use crate::upgradable_ext::{UpgraderExtIntoStream, UpgraderExtRefer};
+
+/*
+Runtime agnostic, e.g.
+
+|dur| async move { async_io::Timer::after(dur).await; }
+|dur| tokio::time::delay_for(dur)
+|dur| tokio1::time::sleep(dur)
+*/
+#[async_trait]
+pub trait Timer {
+    async fn sleep(&self, dur: Duration);
+}
+
+#[async_trait]
+impl<F, Fut> Timer for F
+where
+    F: Fn(Duration) -> Fut + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn sleep(&self, dur: Duration) {
+        (self)(dur).await
+    }
+}
+
+//
+//
+//
+/*
+On timeout the inner upgrade is dropped along with the stream it was given, so the
+UpgradableAsyncStream is left poisoned unless the inner upgrader kept the stream for
+recover_stream. upgrade_with_timeout keeps the stream and lets the upgrade be resumed instead.
+*/
+pub struct TimeoutUpgrader<U, T> {
+    inner: U,
+    timer: T,
+    dur: Duration,
+}
+
+impl<U, T> TimeoutUpgrader<U, T> {
+    pub fn new(inner: U, timer: T, dur: Duration) -> Self {
+        Self { inner, timer, dur }
+    }
+
+    pub fn get_ref(&self) -> &U {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut U {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> U {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<S, U, T> Upgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: Upgrader<S> + Send,
+    T: Timer + Send + Sync,
+{
+    type Output = U::Output;
+    async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+        match Timeout::new(self.inner.upgrade(stream), self.timer.sleep(self.dur)).await {
+            Some(ret) => ret,
+            None => Err(UpgradeError::Timeout.into()),
+        }
+    }
+    fn upgrade_required(&self) -> bool {
+        self.inner.upgrade_required()
+    }
+    fn recover_stream(&mut self) -> Option<S> {
+        self.inner.recover_stream()
+    }
+}
+
+#[async_trait]
+impl<S, U, T> Downgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: Downgrader<S> + Send,
+    U::Output: Send,
+    T: Timer + Send + Sync,
+{
+    async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+        match Timeout::new(self.inner.downgrade(output), self.timer.sleep(self.dur)).await {
+            Some(ret) => ret,
+            None => Err(UpgradeError::Timeout.into()),
+        }
+    }
+    fn downgrade_required(&self) -> bool {
+        self.inner.downgrade_required()
+    }
+    fn recover_output(&mut self) -> Option<Self::Output> {
+        self.inner.recover_output()
+    }
+}
+
+impl<S, U, T> UpgraderExtRefer<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: UpgraderExtRefer<S> + Send,
+    T: Timer + Send + Sync,
+{
+    fn get_ref(output: &Self::Output) -> &S {
+        U::get_ref(output)
+    }
+    fn get_mut(output: &mut Self::Output) -> &mut S {
+        U::get_mut(output)
+    }
+}
+
+impl<S, U, T> UpgraderExtIntoStream<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: UpgraderExtIntoStream<S> + Send,
+    T: Timer + Send + Sync,
+{
+    fn into_stream(output: Self::Output) -> io::Result<S> {
+        U::into_stream(output)
+    }
+}
+
+impl<S, U, T> TlsClientUpgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: TlsClientUpgrader<S> + Send,
+    T: Timer + Send + Sync,
+{
+}
+
+impl<S, U, T> TlsServerUpgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: TlsServerUpgrader<S> + Send,
+    T: Timer + Send + Sync,
+{
+}
+
+impl<S, U, T> Socks5ProxyUpgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: Socks5ProxyUpgrader<S> + Send,
+    T: Timer + Send + Sync,
+{
+}
+
+impl<S, U, T> Socks4ProxyUpgrader<S> for TimeoutUpgrader<U, T>
+where
+    S: Send + 'static,
+    U: Socks4ProxyUpgrader<S> + Send,
+    T: Timer + Send + Sync,
+{
+}
+
+//
+//
+//
+impl<S, SU> UpgradableAsyncStream<S, SU>
+where
+    S: Send + 'static,
+    SU: Upgrader<S> + Send + 'static,
+{
+    /*
+    On timeout the upgrade stays in the stream, it can be resumed by upgrade or dropped with the
+    stream.
+    */
+    pub async fn upgrade_with_timeout<T>(
+        &mut self,
+        dur: Duration,
+        timer: &T,
+    ) -> Result<(), UpgradeError>
+    where
+        T: Timer + ?Sized,
+    {
+        match Timeout::new(self.upgrade(), timer.sleep(dur)).await {
+            Some(ret) => ret,
+            None => Err(UpgradeError::Timeout),
+        }
+    }
+}
+
+impl<S, SU> GradableAsyncStream<S, SU>
+where
+    SU: Upgrader<S> + Downgrader<S> + Send + 'static,
+    SU::Output: Send + 'static,
+{
+    pub async fn downgrade_with_timeout<T>(
+        &mut self,
+        dur: Duration,
+        timer: &T,
+    ) -> Result<(), UpgradeError>
+    where
+        T: Timer + ?Sized,
+    {
+        match Timeout::new(self.downgrade(), timer.sleep(dur)).await {
+            Some(ret) => ret,
+            None => Err(UpgradeError::Timeout),
+        }
+    }
+}
+
+//
+//
+//
+struct Timeout<'a, F> {
+    future: F,
+    delay: Pin<Box<dyn Future<Output = ()> + Send + 'a>>,
+}
+
+impl<'a, F> Timeout<'a, F> {
+    fn new(future: F, delay: Pin<Box<dyn Future<Output = ()> + Send + 'a>>) -> Self {
+        Self { future, delay }
+    }
+}
+
+impl<'a, F> Future for Timeout<'a, F>
+where
+    F: Future + Unpin,
+{
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Poll::Ready(ret) = Pin::new(&mut this.future).poll(cx) {
+            return Poll::Ready(Some(ret));
+        }
+
+        match this.delay.as_mut().poll(cx) {
+            Poll::Ready(_) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
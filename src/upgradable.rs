@@ -1,10 +1,13 @@
-use std::io::{self, SeekFrom};
+use std::future::Future;
+use std::io;
 use std::mem;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use async_trait::async_trait;
-use futures_x_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+use crate::chained::ChainedUpgrader;
+use crate::upgradable_error::UpgradeError;
 
 pub struct UpgradableAsyncStream<S, SU>
 where
@@ -13,32 +16,174 @@ where
     pub(crate) inner: Inner<S, SU>,
 }
 
-pub(crate) enum Inner<S, SU>
-where
-    SU: Upgrader<S>,
-{
+/*
+Shared with PollUpgradableAsyncStream, U and D are what an in-flight upgrade and downgrade are
+kept in.
+*/
+pub(crate) enum State<S, O, SU, U, D> {
     Pending(S, SU),
-    Upgraded(SU::Output, SU),
-    None,
+    Upgrading(U),
+    Upgraded(O, SU),
+    Downgrading(D),
+    Poisoned,
+}
+
+pub(crate) type Inner<S, SU> = State<
+    S,
+    <SU as Upgrader<S>>::Output,
+    SU,
+    Transition<SU, <SU as Upgrader<S>>::Output>,
+    Transition<SU, S>,
+>;
+
+// An in-flight upgrade or downgrade, it owns the upgrader so that it can be kept in Inner and
+// resumed after the caller's future was dropped.
+pub(crate) type Transition<SU, T> = Pin<Box<dyn Future<Output = (SU, io::Result<T>)> + Send>>;
+
+pub(crate) fn poll_transition<SU, T>(
+    mut transition: Transition<SU, T>,
+    cx: &mut Context,
+) -> Result<(SU, io::Result<T>), Transition<SU, T>> {
+    match transition.as_mut().poll(cx) {
+        Poll::Pending => Err(transition),
+        Poll::Ready(ret) => Ok(ret),
+    }
+}
+
+impl<S, O, SU, U, D> State<S, O, SU, U, D> {
+    pub(crate) fn unavailable_error(&self) -> UpgradeError {
+        match self {
+            State::Upgrading(_) | State::Downgrading(_) => UpgradeError::InProgress,
+            _ => UpgradeError::Poisoned,
+        }
+    }
+
+    /*
+    start turns the upgrader and the stream into the in-flight upgrade, poll hands it back while
+    it's pending.
+    */
+    pub(crate) fn poll_upgrade_with(
+        &mut self,
+        cx: &mut Context,
+        upgrade_required: impl FnOnce(&SU) -> bool,
+        start: impl FnOnce(SU, S) -> U,
+        poll: impl FnOnce(U, &mut Context) -> Result<(SU, io::Result<O>), U>,
+        recover_stream: impl FnOnce(&mut SU) -> Option<S>,
+    ) -> Poll<Result<(), UpgradeError>> {
+        let transition = match mem::replace(self, State::Poisoned) {
+            State::Pending(stream, upgrader) => {
+                if !upgrade_required(&upgrader) {
+                    *self = State::Pending(stream, upgrader);
+                    return Poll::Ready(Err(UpgradeError::UpgradeNotRequired));
+                }
+                start(upgrader, stream)
+            }
+            State::Upgrading(transition) => transition,
+            State::Upgraded(stream, upgrader) => {
+                *self = State::Upgraded(stream, upgrader);
+                return Poll::Ready(Err(UpgradeError::AlreadyUpgraded));
+            }
+            state => {
+                let err = state.unavailable_error();
+                *self = state;
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        match poll(transition, cx) {
+            Err(transition) => {
+                *self = State::Upgrading(transition);
+                Poll::Pending
+            }
+            Ok((upgrader, Ok(stream))) => {
+                *self = State::Upgraded(stream, upgrader);
+                Poll::Ready(Ok(()))
+            }
+            Ok((mut upgrader, Err(err))) => {
+                if let Some(stream) = recover_stream(&mut upgrader) {
+                    *self = State::Pending(stream, upgrader);
+                }
+                Poll::Ready(Err(err.into()))
+            }
+        }
+    }
+
+    pub(crate) fn poll_downgrade_with(
+        &mut self,
+        cx: &mut Context,
+        downgrade_required: impl FnOnce(&SU) -> bool,
+        start: impl FnOnce(SU, O) -> D,
+        poll: impl FnOnce(D, &mut Context) -> Result<(SU, io::Result<S>), D>,
+        recover_output: impl FnOnce(&mut SU) -> Option<O>,
+    ) -> Poll<Result<(), UpgradeError>> {
+        let transition = match mem::replace(self, State::Poisoned) {
+            State::Pending(stream, grader) => {
+                *self = State::Pending(stream, grader);
+                return Poll::Ready(Err(UpgradeError::NotUpgraded));
+            }
+            State::Upgraded(stream, grader) => {
+                if !downgrade_required(&grader) {
+                    *self = State::Upgraded(stream, grader);
+                    return Poll::Ready(Err(UpgradeError::DowngradeNotRequired));
+                }
+                start(grader, stream)
+            }
+            State::Downgrading(transition) => transition,
+            state => {
+                let err = state.unavailable_error();
+                *self = state;
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        match poll(transition, cx) {
+            Err(transition) => {
+                *self = State::Downgrading(transition);
+                Poll::Pending
+            }
+            Ok((grader, Ok(stream))) => {
+                *self = State::Pending(stream, grader);
+                Poll::Ready(Ok(()))
+            }
+            Ok((mut grader, Err(err))) => {
+                if let Some(stream) = recover_output(&mut grader) {
+                    *self = State::Upgraded(stream, grader);
+                }
+                Poll::Ready(Err(err.into()))
+            }
+        }
+    }
 }
 
 #[async_trait]
 pub trait Upgrader<S> {
-    type Output: AsyncRead + AsyncWrite + Unpin;
+    type Output;
     async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output>;
+    /// When false, `upgrade` is still called inside a chain and should pass the stream through.
     fn upgrade_required(&self) -> bool {
         true
     }
+    /// Called after a failed `upgrade`, gives back the stream if the upgrader kept it.
+    fn recover_stream(&mut self) -> Option<S> {
+        None
+    }
+    fn and_then<B>(self, next: B) -> ChainedUpgrader<Self, B>
+    where
+        Self: Sized,
+        B: Upgrader<Self::Output>,
+    {
+        ChainedUpgrader::new(self, next)
+    }
 }
 
 #[async_trait]
 impl<S> Upgrader<S> for ()
 where
-    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
 {
     type Output = S;
-    async fn upgrade(&mut self, _: S) -> io::Result<Self::Output> {
-        unreachable!()
+    async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+        Ok(stream)
     }
     fn upgrade_required(&self) -> bool {
         false
@@ -62,202 +207,531 @@ where
     }
 
     pub fn is_upgraded(&self) -> bool {
+        matches!(self.inner, Inner::Upgraded(_, _))
+    }
+
+    pub fn is_upgrading(&self) -> bool {
+        matches!(self.inner, Inner::Upgrading(_))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.inner, Inner::Poisoned)
+    }
+
+    pub fn upgrade_required(&self) -> bool {
         match &self.inner {
-            Inner::Upgraded(_, _) => true,
+            Inner::Pending(_, upgrader) => upgrader.upgrade_required(),
             _ => false,
         }
     }
 
-    pub fn upgrade_required(&self) -> bool {
+    // For state the upgrader keeps, e.g. what the handshake returned. None while upgrading and once poisoned.
+    pub fn upgrader(&self) -> Option<&SU> {
         match &self.inner {
-            Inner::Pending(_, upgrader) => upgrader.upgrade_required(),
-            Inner::Upgraded(_, _) => false,
-            Inner::None => panic!("never"),
+            Inner::Pending(_, upgrader) | Inner::Upgraded(_, upgrader) => Some(upgrader),
+            _ => None,
         }
     }
 
-    pub async fn upgrade(&mut self) -> io::Result<()> {
-        match mem::replace(&mut self.inner, Inner::None) {
-            Inner::Pending(stream, mut upgrader) => {
-                if !upgrader.upgrade_required() {
-                    return Err(io::Error::new(io::ErrorKind::Other, "upgrade not required"));
-                }
-                let stream = upgrader.upgrade(stream).await?;
-                self.inner = Inner::Upgraded(stream, upgrader);
-                Ok(())
-            }
-            Inner::Upgraded(_, _) => Err(io::Error::new(io::ErrorKind::Other, "not allow")),
-            Inner::None => panic!("never"),
+    pub fn upgrader_mut(&mut self) -> Option<&mut SU> {
+        match &mut self.inner {
+            Inner::Pending(_, upgrader) | Inner::Upgraded(_, upgrader) => Some(upgrader),
+            _ => None,
         }
     }
 }
 
-impl<S> UpgradableAsyncStream<S, ()>
+impl<S, SU> UpgradableAsyncStream<S, SU>
 where
-    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+    SU: Upgrader<S> + Send + 'static,
 {
-    pub fn with_stream(stream: S) -> Self {
-        Self {
-            inner: Inner::Pending(stream, ()),
-        }
+    /*
+    The in-flight upgrade is kept in the stream, if the returned future is dropped before it
+    completes, the next call to upgrade (or poll_upgrade) resumes it.
+    */
+    pub fn upgrade(&mut self) -> UpgradeFuture<'_, S, SU> {
+        UpgradeFuture { stream: self }
     }
 
-    pub fn with_upgraded_stream(stream: S) -> Self {
-        Self {
-            inner: Inner::Upgraded(stream, ()),
-        }
+    pub fn poll_upgrade(&mut self, cx: &mut Context) -> Poll<Result<(), UpgradeError>> {
+        self.inner.poll_upgrade_with(
+            cx,
+            |upgrader| upgrader.upgrade_required(),
+            |mut upgrader, stream| {
+                Box::pin(async move {
+                    let ret = upgrader.upgrade(stream).await;
+                    (upgrader, ret)
+                })
+            },
+            poll_transition,
+            |upgrader| upgrader.recover_stream(),
+        )
     }
 }
 
-impl<S, SU> AsyncWrite for UpgradableAsyncStream<S, SU>
+pub struct UpgradeFuture<'a, S, SU>
 where
-    SU: Upgrader<S> + Unpin,
-    S: AsyncWrite + Unpin,
-    SU::Output: AsyncWrite + Unpin,
+    SU: Upgrader<S>,
 {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context,
-        buf: &[u8],
-    ) -> Poll<Result<usize, io::Error>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_write(cx, buf),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_write(cx, buf),
-            Inner::None => panic!("never"),
-        }
-    }
-
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_flush(cx),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_flush(cx),
-            Inner::None => panic!("never"),
-        }
-    }
-
-    #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_close(cx),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_close(cx),
-            Inner::None => panic!("never"),
-        }
-    }
-
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_shutdown(cx),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_shutdown(cx),
-            Inner::None => panic!("never"),
-        }
-    }
+    stream: &'a mut UpgradableAsyncStream<S, SU>,
 }
 
-impl<S, SU> AsyncRead for UpgradableAsyncStream<S, SU>
+impl<'a, S, SU> Future for UpgradeFuture<'a, S, SU>
 where
-    SU: Upgrader<S> + Unpin,
-    S: AsyncRead + Unpin,
-    SU::Output: AsyncRead + Unpin,
+    S: Send + 'static,
+    SU: Upgrader<S> + Send + 'static,
 {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context,
-        buf: &mut [u8],
-    ) -> Poll<Result<usize, io::Error>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_read(cx, buf),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_read(cx, buf),
-            Inner::None => panic!("never"),
-        }
+    type Output = Result<(), UpgradeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().stream.poll_upgrade(cx)
     }
 }
 
-impl<S, SU> AsyncSeek for UpgradableAsyncStream<S, SU>
+impl<S> UpgradableAsyncStream<S, ()>
 where
-    SU: Upgrader<S> + Unpin,
-    S: AsyncSeek + Unpin,
-    SU::Output: AsyncSeek + Unpin,
+    S: Send + 'static,
 {
-    #[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
-    fn poll_seek(self: Pin<&mut Self>, cx: &mut Context, pos: SeekFrom) -> Poll<io::Result<u64>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_seek(cx, pos),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_seek(cx, pos),
-            Inner::None => panic!("never"),
+    pub fn with_stream(stream: S) -> Self {
+        Self {
+            inner: Inner::Pending(stream, ()),
         }
     }
 
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn start_seek(
-        self: Pin<&mut Self>,
-        cx: &mut Context,
-        position: SeekFrom,
-    ) -> Poll<io::Result<()>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).start_seek(cx, position),
-            Inner::Upgraded(s, _) => Pin::new(s).start_seek(cx, position),
-            Inner::None => panic!("never"),
+    pub fn with_upgraded_stream(stream: S) -> Self {
+        Self {
+            inner: Inner::Upgraded(stream, ()),
         }
     }
+}
 
-    #[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
-    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
+//
+//
+//
+/*
+The io traits only look at the Pending and Upgraded states, they are implemented the same way for
+PollUpgradableAsyncStream.
+*/
+macro_rules! upgradable_async_stream_io {
+    ($stream:ident, $upgrader:ident) => {
+        #[cfg(feature = "futures_io")]
+        mod futures_io_impl {
+            use std::io::{self, SeekFrom};
+            use std::pin::Pin;
+            use std::task::{Context, Poll};
 
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_complete(cx),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_complete(cx),
-            Inner::None => panic!("never"),
+            use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+            use super::{$stream, $upgrader, Inner};
+
+            impl<S, SU> AsyncWrite for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncWrite + Unpin,
+                SU::Output: AsyncWrite + Unpin,
+            {
+                fn poll_write(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &[u8],
+                ) -> Poll<Result<usize, io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_write(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_write(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_flush(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<Result<(), io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_flush(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_flush(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_close(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_close(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncRead + Unpin,
+                SU::Output: AsyncRead + Unpin,
+            {
+                fn poll_read(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &mut [u8],
+                ) -> Poll<Result<usize, io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_read(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_read(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncSeek for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncSeek + Unpin,
+                SU::Output: AsyncSeek + Unpin,
+            {
+                fn poll_seek(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    pos: SeekFrom,
+                ) -> Poll<io::Result<u64>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_seek(cx, pos),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_seek(cx, pos),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncBufRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncBufRead + Unpin,
+                SU::Output: AsyncBufRead + Unpin,
+            {
+                fn poll_fill_buf(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<io::Result<&[u8]>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn consume(self: Pin<&mut Self>, amt: usize) {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).consume(amt),
+                        Inner::Upgraded(s, _) => Pin::new(s).consume(amt),
+                        _ => {}
+                    }
+                }
+            }
         }
-    }
-}
 
-impl<S, SU> AsyncBufRead for UpgradableAsyncStream<S, SU>
-where
-    SU: Upgrader<S> + Unpin,
-    S: AsyncBufRead + Unpin,
-    SU::Output: AsyncBufRead + Unpin,
-{
-    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
-
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).poll_fill_buf(cx),
-            Inner::Upgraded(s, _) => Pin::new(s).poll_fill_buf(cx),
-            Inner::None => panic!("never"),
+        #[cfg(feature = "tokio_io")]
+        mod tokio_io_impl {
+            use std::io::{self, SeekFrom};
+            use std::pin::Pin;
+            use std::task::{Context, Poll};
+
+            use tokio::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite};
+
+            use super::{$stream, $upgrader, Inner};
+
+            impl<S, SU> AsyncWrite for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncWrite + Unpin,
+                SU::Output: AsyncWrite + Unpin,
+            {
+                fn poll_write(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &[u8],
+                ) -> Poll<Result<usize, io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_write(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_write(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_flush(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<Result<(), io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_flush(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_flush(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_shutdown(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_shutdown(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncRead + Unpin,
+                SU::Output: AsyncRead + Unpin,
+            {
+                fn poll_read(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &mut [u8],
+                ) -> Poll<Result<usize, io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_read(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_read(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncSeek for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncSeek + Unpin,
+                SU::Output: AsyncSeek + Unpin,
+            {
+                fn start_seek(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    position: SeekFrom,
+                ) -> Poll<io::Result<()>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).start_seek(cx, position),
+                        Inner::Upgraded(s, _) => Pin::new(s).start_seek(cx, position),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_complete(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_complete(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncBufRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncBufRead + Unpin,
+                SU::Output: AsyncBufRead + Unpin,
+            {
+                fn poll_fill_buf(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<io::Result<&[u8]>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn consume(self: Pin<&mut Self>, amt: usize) {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).consume(amt),
+                        Inner::Upgraded(s, _) => Pin::new(s).consume(amt),
+                        _ => {}
+                    }
+                }
+            }
         }
-    }
 
-    fn consume(self: Pin<&mut Self>, amt: usize) {
-        let this = self.get_mut();
-        let inner = &mut this.inner;
+        #[cfg(feature = "tokio1_io")]
+        mod tokio1_io_impl {
+            use std::io::{self, SeekFrom};
+            use std::pin::Pin;
+            use std::task::{Context, Poll};
+
+            use tokio1::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+            use super::{$stream, $upgrader, Inner};
+
+            impl<S, SU> AsyncWrite for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncWrite + Unpin,
+                SU::Output: AsyncWrite + Unpin,
+            {
+                fn poll_write(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &[u8],
+                ) -> Poll<Result<usize, io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_write(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_write(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_flush(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<Result<(), io::Error>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_flush(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_flush(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_shutdown(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_shutdown(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncRead + Unpin,
+                SU::Output: AsyncRead + Unpin,
+            {
+                fn poll_read(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                    buf: &mut ReadBuf<'_>,
+                ) -> Poll<io::Result<()>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_read(cx, buf),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_read(cx, buf),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncSeek for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncSeek + Unpin,
+                SU::Output: AsyncSeek + Unpin,
+            {
+                fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).start_seek(position),
+                        Inner::Upgraded(s, _) => Pin::new(s).start_seek(position),
+                        _ => Err(inner.unavailable_error().into()),
+                    }
+                }
+
+                fn poll_complete(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<u64>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
 
-        match inner {
-            Inner::Pending(s, _) => Pin::new(s).consume(amt),
-            Inner::Upgraded(s, _) => Pin::new(s).consume(amt),
-            Inner::None => panic!("never"),
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_complete(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_complete(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+            }
+
+            impl<S, SU> AsyncBufRead for $stream<S, SU>
+            where
+                SU: $upgrader<S> + Unpin,
+                S: AsyncBufRead + Unpin,
+                SU::Output: AsyncBufRead + Unpin,
+            {
+                fn poll_fill_buf(
+                    self: Pin<&mut Self>,
+                    cx: &mut Context,
+                ) -> Poll<io::Result<&[u8]>> {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        Inner::Upgraded(s, _) => Pin::new(s).poll_fill_buf(cx),
+                        _ => Poll::Ready(Err(inner.unavailable_error().into())),
+                    }
+                }
+
+                fn consume(self: Pin<&mut Self>, amt: usize) {
+                    let this = self.get_mut();
+                    let inner = &mut this.inner;
+
+                    match inner {
+                        Inner::Pending(s, _) => Pin::new(s).consume(amt),
+                        Inner::Upgraded(s, _) => Pin::new(s).consume(amt),
+                        _ => {}
+                    }
+                }
+            }
         }
-    }
+    };
 }
+
+upgradable_async_stream_io!(UpgradableAsyncStream, Upgrader);
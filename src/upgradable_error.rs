@@ -0,0 +1,63 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum UpgradeError {
+    AlreadyUpgraded,
+    NotUpgraded,
+    UpgradeNotRequired,
+    DowngradeNotRequired,
+    InProgress,
+    Poisoned,
+    Timeout,
+    Upgrader(io::Error),
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyUpgraded => write!(f, "already upgraded"),
+            Self::NotUpgraded => write!(f, "not upgraded"),
+            Self::UpgradeNotRequired => write!(f, "upgrade not required"),
+            Self::DowngradeNotRequired => write!(f, "downgrade not required"),
+            Self::InProgress => write!(f, "upgrade or downgrade in progress"),
+            Self::Poisoned => write!(f, "poisoned"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Upgrader(err) => write!(f, "upgrader failed: {}", err),
+        }
+    }
+}
+
+impl error::Error for UpgradeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Upgrader(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// An UpgradeError that went through io::Error (e.g. returned by a nested upgrader) comes back as is.
+impl From<io::Error> for UpgradeError {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            if let Some(Ok(err)) = err.into_inner().map(|inner| inner.downcast::<Self>()) {
+                return *err;
+            }
+            unreachable!()
+        }
+        Self::Upgrader(err)
+    }
+}
+
+impl From<UpgradeError> for io::Error {
+    fn from(err: UpgradeError) -> Self {
+        match err {
+            UpgradeError::Upgrader(err) => err,
+            UpgradeError::Timeout => io::Error::new(io::ErrorKind::TimedOut, err),
+            err => io::Error::other(err),
+        }
+    }
+}
@@ -1,8 +1,7 @@
 use std::io;
 
-use futures_x_io::{AsyncRead, AsyncWrite};
-
 use crate::upgradable::{Inner, UpgradableAsyncStream, Upgrader};
+use crate::upgradable_error::UpgradeError;
 
 //
 //
@@ -14,7 +13,7 @@ pub trait UpgraderExtRefer<S>: Upgrader<S> {
 
 impl<S> UpgraderExtRefer<S> for ()
 where
-    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
 {
     fn get_ref(output: &<Self as Upgrader<S>>::Output) -> &S {
         output
@@ -28,19 +27,34 @@ impl<S, SU> UpgradableAsyncStream<S, SU>
 where
     SU: UpgraderExtRefer<S>,
 {
+    // Panics while upgrading or downgrading and once poisoned, see try_get_ref.
     pub fn get_ref(&self) -> &S {
-        match &self.inner {
-            Inner::Pending(s, _) => &s,
-            Inner::Upgraded(s, _) => SU::get_ref(s),
-            Inner::None => panic!("never"),
+        match self.try_get_ref() {
+            Ok(s) => s,
+            Err(err) => panic!("{}", err),
         }
     }
 
     pub fn get_mut(&mut self) -> &mut S {
+        match self.try_get_mut() {
+            Ok(s) => s,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_get_ref(&self) -> Result<&S, UpgradeError> {
+        match &self.inner {
+            Inner::Pending(s, _) => Ok(s),
+            Inner::Upgraded(s, _) => Ok(SU::get_ref(s)),
+            inner => Err(inner.unavailable_error()),
+        }
+    }
+
+    pub fn try_get_mut(&mut self) -> Result<&mut S, UpgradeError> {
         match &mut self.inner {
-            Inner::Pending(s, _) => s,
-            Inner::Upgraded(s, _) => SU::get_mut(s),
-            Inner::None => panic!("never"),
+            Inner::Pending(s, _) => Ok(s),
+            Inner::Upgraded(s, _) => Ok(SU::get_mut(s)),
+            inner => Err(inner.unavailable_error()),
         }
     }
 }
@@ -54,7 +68,7 @@ pub trait UpgraderExtIntoStream<S>: Upgrader<S> {
 
 impl<S> UpgraderExtIntoStream<S> for ()
 where
-    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
 {
     fn into_stream(output: <Self as Upgrader<S>>::Output) -> io::Result<S> {
         Ok(output)
@@ -69,7 +83,7 @@ where
         match self.inner {
             Inner::Pending(s, _) => Ok(s),
             Inner::Upgraded(s, _) => SU::into_stream(s),
-            Inner::None => panic!("never"),
+            inner => Err(inner.unavailable_error().into()),
         }
     }
 }
@@ -81,11 +95,11 @@ impl<S, SU> UpgradableAsyncStream<S, SU>
 where
     SU: Upgrader<S>,
 {
-    pub fn try_into_stream(self) -> io::Result<S> {
+    pub fn try_into_stream(self) -> Result<S, UpgradeError> {
         match self.inner {
             Inner::Pending(s, _) => Ok(s),
-            Inner::Upgraded(_, _) => Err(io::Error::new(io::ErrorKind::Other, "unimplemented")),
-            Inner::None => panic!("never"),
+            Inner::Upgraded(_, _) => Err(UpgradeError::AlreadyUpgraded),
+            inner => Err(inner.unavailable_error()),
         }
     }
 }
@@ -94,11 +108,11 @@ impl<S, SU> UpgradableAsyncStream<S, SU>
 where
     SU: Upgrader<S>,
 {
-    pub fn try_into_upgraded_stream(self) -> io::Result<SU::Output> {
+    pub fn try_into_upgraded_stream(self) -> Result<SU::Output, UpgradeError> {
         match self.inner {
-            Inner::Pending(_, _) => Err(io::Error::new(io::ErrorKind::Other, "unimplemented")),
+            Inner::Pending(_, _) => Err(UpgradeError::NotUpgraded),
             Inner::Upgraded(s, _) => Ok(s),
-            Inner::None => panic!("never"),
+            inner => Err(inner.unavailable_error()),
         }
     }
 }
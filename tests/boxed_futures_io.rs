@@ -0,0 +1,153 @@
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod boxed_futures_io_tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::boxed::futures_io::{BoxUpgrader, BoxedAsyncStream};
+    use async_stream_packed::{ChainedUpgrader, UpgradableAsyncStream, Upgrader};
+
+    //
+    //
+    //
+    struct NamedUpgrader {
+        name: &'static str,
+        names: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for NamedUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.names.lock().unwrap().push(self.name);
+            Ok(stream)
+        }
+    }
+
+    // Not required, but passes the stream through when run.
+    struct OptionalUpgrader(NamedUpgrader);
+
+    #[async_trait]
+    impl<S> Upgrader<S> for OptionalUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.0.upgrade(stream).await
+        }
+        fn upgrade_required(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn box_upgrader() -> io::Result<()> {
+        block_on(async {
+            let names = Arc::new(Mutex::new(vec![]));
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = BoxUpgrader::new(NamedUpgrader {
+                name: "a",
+                names: names.clone(),
+            });
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            stream.upgrade().await?;
+            assert_eq!(*names.lock().unwrap(), vec!["a"]);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn steps() -> io::Result<()> {
+        block_on(async {
+            let names = Arc::new(Mutex::new(vec![]));
+
+            let cursor: BoxedAsyncStream = Box::pin(Cursor::new(b"foo".to_vec()));
+            let steps = vec![
+                BoxUpgrader::new(NamedUpgrader {
+                    name: "a",
+                    names: names.clone(),
+                }),
+                BoxUpgrader::new(()),
+                BoxUpgrader::new(NamedUpgrader {
+                    name: "b",
+                    names: names.clone(),
+                }),
+            ];
+            let mut stream = UpgradableAsyncStream::new(cursor, steps);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert_eq!(*names.lock().unwrap(), vec!["a", "b"]);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            //
+            let cursor: BoxedAsyncStream = Box::pin(Cursor::new(b"foo".to_vec()));
+            let stream = UpgradableAsyncStream::new(cursor, vec![BoxUpgrader::new(())]);
+            assert!(!stream.upgrade_required());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn steps_not_required() -> io::Result<()> {
+        block_on(async {
+            let names = Arc::new(Mutex::new(vec![]));
+
+            let cursor: BoxedAsyncStream = Box::pin(Cursor::new(b"foo".to_vec()));
+            let steps = vec![
+                BoxUpgrader::new(NamedUpgrader {
+                    name: "a",
+                    names: names.clone(),
+                }),
+                BoxUpgrader::new(OptionalUpgrader(NamedUpgrader {
+                    name: "b",
+                    names: names.clone(),
+                })),
+            ];
+            let mut stream = UpgradableAsyncStream::new(cursor, steps);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            // Same as ChainedUpgrader.
+            assert_eq!(*names.lock().unwrap(), vec!["a", "b"]);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            //
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = ChainedUpgrader::new(
+                NamedUpgrader {
+                    name: "c",
+                    names: names.clone(),
+                },
+                OptionalUpgrader(NamedUpgrader {
+                    name: "d",
+                    names: names.clone(),
+                }),
+            );
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            stream.upgrade().await?;
+            assert_eq!(*names.lock().unwrap(), vec!["a", "b", "c", "d"]);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
+mod boxed_tokio_io_tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    use async_stream_packed::boxed::tokio_io::{BoxUpgrader, BoxedAsyncStream};
+    use async_stream_packed::{UpgradableAsyncStream, Upgrader};
+
+    //
+    //
+    //
+    struct NamedUpgrader {
+        name: &'static str,
+        names: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for NamedUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.names.lock().unwrap().push(self.name);
+            Ok(stream)
+        }
+    }
+
+    #[test]
+    fn box_upgrader() -> io::Result<()> {
+        block_on(async {
+            let names = Arc::new(Mutex::new(vec![]));
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = BoxUpgrader::new(NamedUpgrader {
+                name: "a",
+                names: names.clone(),
+            });
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            stream.upgrade().await?;
+            assert_eq!(*names.lock().unwrap(), vec!["a"]);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn steps() -> io::Result<()> {
+        block_on(async {
+            let names = Arc::new(Mutex::new(vec![]));
+
+            let cursor: BoxedAsyncStream = Box::pin(Cursor::new(b"foo".to_vec()));
+            let steps = vec![
+                BoxUpgrader::new(NamedUpgrader {
+                    name: "a",
+                    names: names.clone(),
+                }),
+                BoxUpgrader::new(()),
+                BoxUpgrader::new(NamedUpgrader {
+                    name: "b",
+                    names: names.clone(),
+                }),
+            ];
+            let mut stream = UpgradableAsyncStream::new(cursor, steps);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert_eq!(*names.lock().unwrap(), vec!["a", "b"]);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            //
+            let cursor: BoxedAsyncStream = Box::pin(Cursor::new(b"foo".to_vec()));
+            let stream = UpgradableAsyncStream::new(cursor, vec![BoxUpgrader::new(())]);
+            assert!(!stream.upgrade_required());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod chained_futures_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+
+    use async_stream_packed::{
+        ChainedUpgrader, Downgrader, UpgradableAsyncStream, UpgradeError, Upgrader,
+        UpgraderExtIntoStream, UpgraderExtRefer,
+    };
+
+    //
+    //
+    //
+    struct Wrapped<S> {
+        stream: S,
+        layer: &'static str,
+    }
+
+    struct WrapGrader {
+        layer: &'static str,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for WrapGrader
+    where
+        S: Send + 'static,
+    {
+        type Output = Wrapped<S>;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(Wrapped {
+                stream,
+                layer: self.layer,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for WrapGrader
+    where
+        S: Send + 'static,
+    {
+        async fn downgrade(&mut self, output: Self::Output) -> io::Result<S> {
+            Ok(output.stream)
+        }
+    }
+
+    impl<S> UpgraderExtRefer<S> for WrapGrader
+    where
+        S: Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            &output.stream
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            &mut output.stream
+        }
+    }
+
+    impl<S> UpgraderExtIntoStream<S> for WrapGrader
+    where
+        S: Send + 'static,
+    {
+        fn into_stream(output: Self::Output) -> io::Result<S> {
+            Ok(output.stream)
+        }
+    }
+
+    struct FailedUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for FailedUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, _stream: S) -> io::Result<Self::Output> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "foo"))
+        }
+    }
+
+    #[test]
+    fn upgrade() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = <WrapGrader as Upgrader<Cursor<Vec<u8>>>>::and_then(
+                WrapGrader { layer: "a" },
+                WrapGrader { layer: "b" },
+            );
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+            assert_eq!(stream.get_ref().get_ref(), b"foo");
+
+            let output = stream.try_into_upgraded_stream()?;
+            assert_eq!(output.layer, "b");
+            assert_eq!(output.stream.layer, "a");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn downgrade() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader =
+                ChainedUpgrader::new(WrapGrader { layer: "a" }, WrapGrader { layer: "b" });
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            stream.upgrade().await?;
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+            assert_eq!(stream.get_ref().get_ref(), b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn into_stream() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader =
+                ChainedUpgrader::new(WrapGrader { layer: "a" }, WrapGrader { layer: "b" });
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            stream.upgrade().await?;
+            let cursor = stream.into_stream()?;
+            assert_eq!(cursor.get_ref(), b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn upgrade_failed() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = ChainedUpgrader::new(WrapGrader { layer: "a" }, FailedUpgrader {});
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Upgrader(_)));
+            assert!(stream.is_poisoned());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn upgrade_required() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = ChainedUpgrader::new(WrapGrader { layer: "a" }, ());
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert_eq!(stream.get_ref().get_ref(), b"foo");
+            assert!(stream.downgrade_required());
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = ChainedUpgrader::new((), WrapGrader { layer: "b" });
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            let output = stream.try_into_upgraded_stream()?;
+            assert_eq!(output.layer, "b");
+            assert_eq!(output.stream.get_ref(), b"foo");
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader = <() as Upgrader<Cursor<Vec<u8>>>>::and_then((), ());
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(!stream.upgrade_required());
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::UpgradeNotRequired));
+
+            Ok(())
+        })
+    }
+}
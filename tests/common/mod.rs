@@ -0,0 +1,248 @@
+#![allow(dead_code)]
+
+use std::io::{self, Cursor, Read as _};
+use std::sync::{Arc, Mutex};
+
+/*
+Replies with the given bytes and records what was written, writes after close fail.
+*/
+pub struct ScriptedStream<W = Vec<u8>> {
+    pub replies: Cursor<Vec<u8>>,
+    pub written: W,
+    pub closed: bool,
+    // Holds the replies back until the write half was closed.
+    pub reply_after_close: bool,
+    // Splits the replies into segments of this size and pends once between them, like a peer
+    // whose next segment is still in flight.
+    pub segment: Option<usize>,
+    in_flight: bool,
+}
+
+impl ScriptedStream {
+    pub fn new(replies: impl AsRef<[u8]>) -> Self {
+        Self::with_written(replies, vec![])
+    }
+}
+
+impl<W> ScriptedStream<W>
+where
+    W: Written,
+{
+    // Lets the written bytes be shared with something the stream is moved into.
+    pub fn with_written(replies: impl AsRef<[u8]>, written: W) -> Self {
+        Self {
+            replies: Cursor::new(replies.as_ref().to_vec()),
+            written,
+            closed: false,
+            reply_after_close: false,
+            segment: None,
+            in_flight: false,
+        }
+    }
+
+    fn write_recorded(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        self.written.record(buf);
+        Ok(buf.len())
+    }
+
+    // None while the replies are held back.
+    fn read_reply(&mut self, buf: &mut [u8]) -> Option<io::Result<usize>> {
+        if self.reply_after_close && !self.closed {
+            return None;
+        }
+        match self.segment {
+            Some(_) if self.in_flight => {
+                self.in_flight = false;
+                None
+            }
+            Some(n) => {
+                self.in_flight = true;
+                let n = n.min(buf.len());
+                Some(self.replies.read(&mut buf[..n]))
+            }
+            None => Some(self.replies.read(buf)),
+        }
+    }
+}
+
+pub trait Written {
+    fn record(&mut self, buf: &[u8]);
+}
+
+impl Written for Vec<u8> {
+    fn record(&mut self, buf: &[u8]) {
+        self.extend_from_slice(buf)
+    }
+}
+
+impl Written for Arc<Mutex<Vec<u8>>> {
+    fn record(&mut self, buf: &[u8]) {
+        self.lock().unwrap().extend_from_slice(buf)
+    }
+}
+
+//
+//
+//
+#[cfg(feature = "futures_io")]
+mod futures_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_lite::{AsyncRead, AsyncWrite};
+
+    use super::{ScriptedStream, Written};
+
+    impl<W> AsyncWrite for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().write_recorded(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<W> AsyncRead for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            match self.get_mut().read_reply(buf) {
+                Some(ret) => Poll::Ready(ret),
+                None => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio_io")]
+mod tokio_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    use super::{ScriptedStream, Written};
+
+    impl<W> AsyncWrite for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().write_recorded(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<W> AsyncRead for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            match self.get_mut().read_reply(buf) {
+                Some(ret) => Poll::Ready(ret),
+                None => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio1_io")]
+mod tokio1_io {
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio1::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use super::{ScriptedStream, Written};
+
+    impl<W> AsyncWrite for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().write_recorded(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<W> AsyncRead for ScriptedStream<W>
+    where
+        W: Written + Unpin,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            match self.get_mut().read_reply(buf.initialize_unfilled()) {
+                Some(Ok(n)) => {
+                    buf.advance(n);
+                    Poll::Ready(Ok(()))
+                }
+                Some(Err(err)) => Poll::Ready(Err(err)),
+                None => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
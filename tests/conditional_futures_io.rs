@@ -0,0 +1,159 @@
+#[cfg(all(feature = "upgradable", feature = "unionable", feature = "futures_io"))]
+mod conditional_futures_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use either::Either;
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+    use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+    use async_stream_packed::{
+        Downgrader, UnionableAsyncStream, UpgradableAsyncStream, UpgradeError, Upgrader,
+        UpgraderExtIntoStream, UpgraderExtRefer,
+    };
+
+    //
+    //
+    //
+    struct SimpleGrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleGrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for SimpleGrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        async fn downgrade(&mut self, output: S) -> io::Result<S> {
+            Ok(output)
+        }
+    }
+
+    impl<S> UpgraderExtRefer<S> for SimpleGrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output
+        }
+    }
+
+    impl<S> UpgraderExtIntoStream<S> for SimpleGrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        fn into_stream(output: Self::Output) -> io::Result<S> {
+            Ok(output)
+        }
+    }
+
+    #[test]
+    fn option_some() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream = UpgradableAsyncStream::new(cursor, Some(SimpleGrader {}));
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+            assert_eq!(stream.get_ref().get_ref(), b"foo");
+
+            let mut buf = vec![0; 3];
+            stream.read(&mut buf).await?;
+            assert_eq!(buf, b"foo");
+
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            stream.upgrade().await?;
+            let cursor = stream.into_stream()?;
+            assert_eq!(cursor.get_ref(), b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn option_none() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream = UpgradableAsyncStream::new(cursor, Option::<SimpleGrader>::None);
+            assert!(!stream.upgrade_required());
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::UpgradeNotRequired));
+            assert!(!stream.is_upgraded());
+
+            let mut buf = vec![0; 3];
+            stream.read(&mut buf).await?;
+            assert_eq!(buf, b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn either() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader: Either<SimpleGrader, ()> = Either::Left(SimpleGrader {});
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+            assert_eq!(stream.get_ref().get_ref(), b"foo");
+
+            let mut buf = vec![0; 3];
+            stream.read(&mut buf).await?;
+            assert_eq!(buf, b"foo");
+
+            let output = stream.try_into_upgraded_stream()?;
+            assert!(output.get_ref().is_left());
+
+            //
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader: Either<SimpleGrader, ()> = Either::Right(());
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+            assert!(!stream.upgrade_required());
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::UpgradeNotRequired));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn mismatched_output() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let upgrader: Either<SimpleGrader, SimpleGrader> = Either::Left(SimpleGrader {});
+            let mut stream = UpgradableAsyncStream::with_upgraded_stream_and_upgrader(
+                UnionableAsyncStream::the_other(cursor),
+                upgrader,
+            );
+            let err = stream.downgrade().await.err().unwrap();
+            assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidInput);
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let err = Option::<SimpleGrader>::None
+                .downgrade(UnionableAsyncStream::one(cursor))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "unionable", feature = "tokio1_io"))]
+mod conditional_tokio1_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use either::Either;
+    use futures_lite::future::block_on;
+    use tokio1::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    use async_stream_packed::{UpgradableAsyncStream, Upgrader};
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    struct MarkerUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for MarkerUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+            stream.write_all(b"<up>").await?;
+            Ok(stream)
+        }
+    }
+
+    #[test]
+    fn option() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"foo");
+            let mut stream = UpgradableAsyncStream::new(stream, Some(MarkerUpgrader {}));
+            assert!(stream.upgrade_required());
+            stream.upgrade().await?;
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            let output = stream.try_into_upgraded_stream()?;
+            match output.get_ref() {
+                Either::Left(stream) => assert_eq!(stream.written, b"<up>"),
+                Either::Right(_) => panic!(),
+            }
+
+            //
+            let stream = ScriptedStream::new(b"foo");
+            let stream = UpgradableAsyncStream::new(stream, Option::<MarkerUpgrader>::None);
+            assert!(!stream.upgrade_required());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn either() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"foo");
+            let upgrader: Either<(), MarkerUpgrader> = Either::Right(MarkerUpgrader {});
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            let output = stream.try_into_upgraded_stream()?;
+            match output.get_ref() {
+                Either::Left(_) => panic!(),
+                Either::Right(stream) => assert_eq!(stream.written, b"<up>"),
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -1,17 +1,17 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod gradable_futures_io_tests {
+    use std::future::Future;
     use std::io;
+    use std::pin::Pin;
+    use std::task::Poll;
 
+    use async_channel::{unbounded, Receiver};
     use async_trait::async_trait;
-    use futures_lite::future::block_on;
+    use futures_lite::future::{self, block_on};
     use futures_lite::io::Cursor;
     use futures_lite::{AsyncRead, AsyncWrite};
 
-    use async_stream_packed::{Downgrader, GradableAsyncStream, Upgrader};
+    use async_stream_packed::{Downgrader, GradableAsyncStream, UpgradeError, Upgrader};
 
     //
     //
@@ -48,13 +48,13 @@ mod gradable_futures_io_tests {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream =
                 GradableAsyncStream::with_upgraded_stream_and_grader(cursor, SimpleGrader {});
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.upgrade_required(), false);
-            assert_eq!(stream.downgrade_required(), true);
+            assert!(stream.is_upgraded());
+            assert!(!stream.upgrade_required());
+            assert!(stream.downgrade_required());
             stream.downgrade().await?;
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), true);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(stream.upgrade_required());
+            assert!(!stream.downgrade_required());
 
             Ok(())
         })
@@ -65,17 +65,17 @@ mod gradable_futures_io_tests {
         block_on(async {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream = GradableAsyncStream::new(cursor, SimpleGrader {});
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), true);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(stream.upgrade_required());
+            assert!(!stream.downgrade_required());
             stream.upgrade().await?;
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.upgrade_required(), false);
-            assert_eq!(stream.downgrade_required(), true);
+            assert!(stream.is_upgraded());
+            assert!(!stream.upgrade_required());
+            assert!(stream.downgrade_required());
             stream.downgrade().await?;
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), true);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(stream.upgrade_required());
+            assert!(!stream.downgrade_required());
 
             Ok(())
         })
@@ -119,20 +119,18 @@ mod gradable_futures_io_tests {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream =
                 GradableAsyncStream::new(cursor, SimpleGraderWithNotDowngradeRequired {});
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(!stream.downgrade_required());
             let err = stream.downgrade().await.err().unwrap();
-            assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "not allow");
+            assert!(matches!(err, UpgradeError::NotUpgraded));
 
             //
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream = GradableAsyncStream::with_upgraded_stream(cursor);
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(stream.is_upgraded());
+            assert!(!stream.downgrade_required());
             let err = stream.downgrade().await.err().unwrap();
-            assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "downgrade not required");
+            assert!(matches!(err, UpgradeError::DowngradeNotRequired));
 
             //
             let cursor = Cursor::new(Vec::<u8>::new());
@@ -140,11 +138,10 @@ mod gradable_futures_io_tests {
                 cursor,
                 SimpleGraderWithNotDowngradeRequired {},
             );
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.downgrade_required(), false);
+            assert!(stream.is_upgraded());
+            assert!(!stream.downgrade_required());
             let err = stream.downgrade().await.err().unwrap();
-            assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "downgrade not required");
+            assert!(matches!(err, UpgradeError::DowngradeNotRequired));
 
             Ok(())
         })
@@ -175,10 +172,7 @@ mod gradable_futures_io_tests {
             }
         }
         fn upgrade_required(&self) -> bool {
-            match self.upgrade_count {
-                0 => true,
-                _ => false,
-            }
+            self.upgrade_count == 0
         }
     }
 
@@ -200,10 +194,7 @@ mod gradable_futures_io_tests {
             }
         }
         fn downgrade_required(&self) -> bool {
-            match self.downgrade_count {
-                0 => true,
-                _ => false,
-            }
+            self.downgrade_count == 0
         }
     }
 
@@ -217,12 +208,145 @@ mod gradable_futures_io_tests {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream = GradableAsyncStream::new(cursor, grader);
 
-            assert_eq!(stream.is_upgraded(), false);
+            assert!(!stream.is_upgraded());
             stream.upgrade().await?;
-            assert_eq!(stream.is_upgraded(), true);
+            assert!(stream.is_upgraded());
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+            assert!(!stream.upgrade_required());
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    #[derive(Default)]
+    struct SimpleGraderWithRecoverOutput<S> {
+        downgrade_count: usize,
+        output: Option<S>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleGraderWithRecoverOutput<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for SimpleGraderWithRecoverOutput<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        async fn downgrade(&mut self, output: S) -> io::Result<S> {
+            self.downgrade_count += 1;
+            match self.downgrade_count {
+                1 => {
+                    self.output = Some(output);
+                    Err(io::Error::new(io::ErrorKind::TimedOut, "foo"))
+                }
+                _ => Ok(output),
+            }
+        }
+        fn recover_output(&mut self) -> Option<S> {
+            self.output.take()
+        }
+    }
+
+    #[test]
+    fn downgrade_failed() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = GradableAsyncStream::with_upgraded_stream_and_grader(
+                cursor,
+                SimpleGraderWithRecoverOutput::default(),
+            );
+            let err = stream.downgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Upgrader(_)));
+            assert!(!stream.is_poisoned());
+            assert!(stream.is_upgraded());
+
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct SimpleGraderWithTwoAwaitPoints {
+        receiver: Receiver<()>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleGraderWithTwoAwaitPoints
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for SimpleGraderWithTwoAwaitPoints
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        async fn downgrade(&mut self, output: S) -> io::Result<S> {
+            self.receiver.recv().await.unwrap();
+            self.receiver.recv().await.unwrap();
+            Ok(output)
+        }
+    }
+
+    #[test]
+    fn downgrade_future_dropped() -> io::Result<()> {
+        block_on(async {
+            let (sender, receiver) = unbounded();
+
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = GradableAsyncStream::with_upgraded_stream_and_grader(
+                cursor,
+                SimpleGraderWithTwoAwaitPoints { receiver },
+            );
+
+            // dropped at the first await point
+            future::poll_fn(|cx| {
+                let mut downgrade = stream.downgrade();
+                assert!(Pin::new(&mut downgrade).poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            assert!(stream.is_downgrading());
+            assert!(!stream.downgrade_required());
+
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::InProgress));
+
+            // dropped at the second await point
+            sender.send(()).await.unwrap();
+            future::poll_fn(|cx| {
+                assert!(stream.poll_downgrade(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            assert!(stream.is_downgrading());
+
+            // resumed
+            sender.send(()).await.unwrap();
             stream.downgrade().await?;
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(!stream.is_downgrading());
+            assert!(!stream.is_upgraded());
 
             Ok(())
         })
@@ -0,0 +1,255 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod http_connect_futures_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::http_connect::futures_io::{
+        HttpConnectServerGrader, HttpConnectTunnelGrader,
+    };
+    use async_stream_packed::{
+        HttpClientInnerStream, HttpClientProxy, HttpConnectError, HttpConnectResponse, TargetAddr,
+        UpgradableAsyncStream,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn connect() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(
+                b"HTTP/1.1 200 Connection established\r\nVia: proxy\r\n\r\nfoo",
+            );
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_user_agent("curl/7.68.0")
+                .with_proxy_authorization("Basic dXNlcjpwYXNz");
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8(stream.get_ref().written.clone()).unwrap(),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nUser-Agent: curl/7.68.0\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn connect_with_host() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.0 200 OK\r\n\r\n");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_header("host", "example.com");
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8(stream.get_ref().written.clone()).unwrap(),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nhost: example.com\r\n\r\n"
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn unexpected_status() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 6\r\n\r\ndenied");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<HttpConnectError>())
+            {
+                Some(HttpConnectError::Status(res)) => {
+                    assert_eq!(res.status, 407);
+                    assert_eq!(res.reason, "Proxy Authentication Required");
+                    assert_eq!(
+                        res.header("proxy-authenticate"),
+                        Some("Basic realm=\"proxy\"")
+                    );
+                    assert_eq!(res.body, b"denied");
+                }
+                _ => panic!(),
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn invalid_response() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"SSH-2.0-OpenSSH\r\n\r\n");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn header_injection() -> io::Result<()> {
+        block_on(async {
+            for grader in [
+                HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                    .with_user_agent("curl\r\nX-Injected: 1"),
+                HttpConnectTunnelGrader::new(("httpbin.org", 443)).with_header("X-Foo\n", "bar"),
+                HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                    .with_proxy_authorization("Basic\0"),
+                HttpConnectTunnelGrader::new(("httpbin.org\r\nX-Injected: 1\r\n", 443)),
+            ] {
+                let stream = ScriptedStream::new(b"HTTP/1.1 200 OK\r\n\r\n");
+                let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+                let err = io::Error::from(stream.upgrade().await.err().unwrap());
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            }
+
+            let stream =
+                ScriptedStream::new(b"CONNECT 127.0.0.1:22 HTTP/1.1\r\nHost: 127.0.0.1:22\r\n\r\n");
+            let grader = HttpConnectServerGrader::new(|_| {
+                Err(HttpConnectResponse::new(403, "Forbidden")
+                    .with_header("X-Reason", "denied\r\nSet-Cookie: a=b"))
+            });
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn http_client_proxy() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 200 OK\r\n\r\nfoo");
+            let mut stream = HttpClientInnerStream::<_, (), _, ()>::new(
+                stream,
+                Some(HttpClientProxy::http(HttpConnectTunnelGrader::new((
+                    "httpbin.org",
+                    80,
+                )))),
+                None,
+            )
+            .await?;
+            assert_eq!(stream.transport().to_string(), "http-proxy");
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn server_allow() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nUser-Agent: curl/7.68.0\r\n\r\nfoo");
+            let grader = HttpConnectServerGrader::new(|req| {
+                assert_eq!(req.header("user-agent"), Some("curl/7.68.0"));
+                match req.target() {
+                    Some(TargetAddr::Domain(host, 443)) if host == "httpbin.org" => Ok(()),
+                    _ => Err(HttpConnectResponse::new(403, "Forbidden")),
+                }
+            });
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8(stream.get_ref().written.clone()).unwrap(),
+                "HTTP/1.1 200 Connection Established\r\n\r\n"
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn server_deny() -> io::Result<()> {
+        block_on(async {
+            let stream =
+                ScriptedStream::new(b"CONNECT 127.0.0.1:22 HTTP/1.1\r\nHost: 127.0.0.1:22\r\n\r\n");
+            let grader =
+                HttpConnectServerGrader::new(|req| match req.header("Proxy-Authorization") {
+                    Some(_) => Ok(()),
+                    None => Err(
+                        HttpConnectResponse::new(407, "Proxy Authentication Required")
+                            .with_header("Proxy-Authenticate", "Basic realm=\"proxy\""),
+                    ),
+                });
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<HttpConnectError>())
+            {
+                Some(HttpConnectError::Denied(res)) => assert_eq!(res.status, 407),
+                _ => panic!(),
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn server_invalid_request() -> io::Result<()> {
+        block_on(async {
+            for (req, status) in &[
+                (&b"GET http://httpbin.org/ip HTTP/1.1\r\n\r\n"[..], 405),
+                (&b"CONNECT httpbin.org HTTP/1.1\r\n\r\n"[..], 400),
+            ] {
+                let stream = ScriptedStream::new(req);
+                let grader = HttpConnectServerGrader::new(|_| Ok(()));
+                let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+                let err = io::Error::from(stream.upgrade().await.err().unwrap());
+                match err
+                    .get_ref()
+                    .and_then(|err| err.downcast_ref::<HttpConnectError>())
+                {
+                    Some(HttpConnectError::Denied(res)) => assert_eq!(res.status, *status),
+                    _ => panic!(),
+                }
+            }
+
+            let stream = ScriptedStream::new(b"SSH-2.0-OpenSSH\r\n\r\n");
+            let grader = HttpConnectServerGrader::new(|_| Ok(()));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            Ok(())
+        })
+    }
+}
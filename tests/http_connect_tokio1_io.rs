@@ -0,0 +1,38 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod http_connect_tokio1_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use tokio1::io::AsyncReadExt;
+
+    use async_stream_packed::http_connect::tokio1_io::HttpConnectTunnelGrader;
+    use async_stream_packed::UpgradableAsyncStream;
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn connect() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 200 OK\r\n\r\nfoo");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 80));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                b"CONNECT httpbin.org:80 HTTP/1.1\r\nHost: httpbin.org:80\r\n\r\n".to_vec()
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+}
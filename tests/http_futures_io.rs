@@ -1,8 +1,4 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod http_futures_io_tests {
     use std::io;
     use std::pin::Pin;
@@ -16,8 +12,8 @@ mod http_futures_io_tests {
     use futures_lite::{AsyncReadExt, AsyncWriteExt};
 
     use async_stream_packed::{
-        Downgrader, HttpClientInnerStream, HttpClientProxy, HttpTunnelClientGrader,
-        TlsClientUpgrader, Upgrader,
+        Downgrader, HttpClientInnerStream, HttpClientProxy, HttpProxyKind, HttpTransport,
+        HttpTunnelClientGrader, TlsClientUpgrader, Upgrader,
     };
 
     //
@@ -270,7 +266,9 @@ mod http_futures_io_tests {
             let cursor = Cursor::new(b"foo".to_vec());
             let mut stream =
                 HttpClientInnerStream::<_, (), (), ()>::new(cursor, None, None).await?;
-            assert!(stream.is_case1());
+            assert_eq!(stream.transport().to_string(), "direct");
+            assert!(!stream.transport().via_proxy());
+            assert!(!stream.transport().is_tls());
 
             stream.write(b"").await?;
 
@@ -295,7 +293,7 @@ mod http_futures_io_tests {
                 Some(SimpleTlsClientUpgrader::new(sender.clone())),
             )
             .await?;
-            assert!(stream.is_case2());
+            assert_eq!(stream.transport().to_string(), "direct+tls");
 
             stream.write(b"").await?;
 
@@ -336,7 +334,7 @@ mod http_futures_io_tests {
                 None,
             )
             .await?;
-            assert!(stream.is_case3());
+            assert_eq!(stream.transport().to_string(), "http-proxy");
 
             stream.write(b"").await?;
 
@@ -373,7 +371,7 @@ mod http_futures_io_tests {
                 Some(SimpleTlsClientUpgrader::new(sender.clone())),
             )
             .await?;
-            assert!(stream.is_case4());
+            assert_eq!(stream.transport().to_string(), "http-proxy+tls");
 
             stream.write(b"").await?;
 
@@ -423,7 +421,7 @@ mod http_futures_io_tests {
                 None,
             )
             .await?;
-            assert!(stream.is_case5());
+            assert_eq!(stream.transport().to_string(), "https-proxy");
 
             stream.write(b"").await?;
 
@@ -473,7 +471,17 @@ mod http_futures_io_tests {
                 Some(SimpleTlsClientUpgrader::new(sender.clone())),
             )
             .await?;
-            assert!(stream.is_case6());
+            assert_eq!(
+                stream.transport(),
+                HttpTransport {
+                    proxy: Some(HttpProxyKind::Https),
+                    origin_tls: true,
+                }
+            );
+            assert_eq!(stream.transport().to_string(), "https-proxy+tls");
+            assert!(stream.transport().via_proxy());
+            assert!(stream.transport().is_tls());
+            assert_eq!(stream.transport().proxy_kind(), Some(HttpProxyKind::Https));
 
             stream.write(b"").await?;
 
@@ -1,9 +1,5 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod http_tunnel_futures_io_tests {
     #![allow(unused_imports)]
-    use async_stream_packed::HttpTunnelClientGrader;
+    use async_stream_packed::{HttpTunnelClientGrader, HttpTunnelServerGrader};
 }
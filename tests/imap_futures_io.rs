@@ -1,9 +1,8 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod imap_futures_io_tests {
+    use std::collections::VecDeque;
     use std::io;
     use std::pin::Pin;
     use std::task::{Context, Poll};
@@ -15,7 +14,15 @@ mod imap_futures_io_tests {
     use futures_lite::{AsyncRead, AsyncWrite};
     use futures_lite::{AsyncReadExt, AsyncWriteExt};
 
-    use async_stream_packed::{ImapClientInnerStream, TlsClientUpgrader, Upgrader};
+    use async_stream_packed::imap::futures_io::{
+        ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader,
+    };
+    use async_stream_packed::{
+        ImapClientInnerStream, ImapError, TlsClientUpgrader, TlsServerUpgrader,
+        UpgradableAsyncStream, Upgrader, UpgraderExtRefer,
+    };
+
+    use super::common::ScriptedStream;
 
     //
     //
@@ -181,4 +188,230 @@ mod imap_futures_io_tests {
             Ok(())
         })
     }
+
+    //
+    //
+    //
+    struct MarkerTlsUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+            stream.write_all(b"<tls>").await?;
+            Ok(stream)
+        }
+    }
+
+    impl<S> TlsServerUpgrader<S> for MarkerTlsUpgrader where S: AsyncWrite + Unpin + Send + 'static {}
+
+    impl<S> TlsClientUpgrader<S> for MarkerTlsUpgrader where S: AsyncWrite + Unpin + Send + 'static {}
+
+    impl<S> UpgraderExtRefer<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output
+        }
+    }
+
+    #[test]
+    fn imap_server_starttls() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(
+                b"a1 CAPABILITY\r\na2 LOGIN foo bar\r\nfoo\r\na3 noop\r\na4 STARTTLS\r\n",
+            );
+            let mut stream = ImapServerInnerStream::with_imap_server(stream, MarkerTlsUpgrader {});
+            stream.upgrade().await?;
+
+            let expected = [
+                "* OK [CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED] IMAP4rev1 Service Ready\r\n",
+                "* CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED\r\n",
+                "a1 OK CAPABILITY completed\r\n",
+                "a2 NO [PRIVACYREQUIRED] Run STARTTLS first\r\n",
+                "* BAD Invalid command\r\n",
+                "a3 OK NOOP completed\r\n",
+                "a4 OK Begin TLS negotiation now\r\n",
+                "<tls>",
+            ]
+            .concat();
+            assert_eq!(String::from_utf8_lossy(&stream.get_ref().written), expected);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn imap_server_starttls_pipelined() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"a1 STARTTLS\r\na2 LOGIN foo bar\r\n");
+            let upgrader = ImapStartTlsServerUpgrader::new(MarkerTlsUpgrader {})
+                .with_greeting("Hi")
+                .with_capability("ID");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let stream = ScriptedStream::new(b"a1 LOGOUT\r\n");
+            let mut upgrader = ImapStartTlsServerUpgrader::new(MarkerTlsUpgrader {});
+            let err = upgrader.upgrade(stream).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    // Every write makes the next reply readable, like a server answering commands.
+    struct ExchangeStream {
+        replies: VecDeque<Vec<u8>>,
+        readable: Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl ExchangeStream {
+        fn new(replies: &[&[u8]]) -> Self {
+            let mut replies: VecDeque<Vec<u8>> = replies.iter().map(|r| r.to_vec()).collect();
+            Self {
+                readable: Cursor::new(replies.pop_front().unwrap_or_default()),
+                replies,
+                written: vec![],
+            }
+        }
+    }
+
+    impl AsyncWrite for ExchangeStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            this.written.extend_from_slice(buf);
+            if let Some(reply) = this.replies.pop_front() {
+                let pos = this.readable.position() as usize;
+                let mut readable = this.readable.get_ref()[pos..].to_vec();
+                readable.extend_from_slice(&reply);
+                this.readable = Cursor::new(readable);
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for ExchangeStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().readable).poll_read(cx, buf)
+        }
+    }
+
+    #[test]
+    fn imap_start_tls() -> io::Result<()> {
+        block_on(async {
+            let stream = ExchangeStream::new(&[
+                b"* OK [CAPABILITY IMAP4rev1 STARTTLS LOGINDISABLED] ready\r\n",
+                b"* OK [ALERT] injected\r\na2 OK Begin TLS negotiation now\r\n",
+                b"",
+            ]);
+            let mut upgrader = ImapStartTlsUpgrader::new(MarkerTlsUpgrader {});
+            let stream = upgrader.upgrade(stream).await?;
+            assert_eq!(stream.written, b"a2 STARTTLS\r\n<tls>".to_vec());
+
+            let stream = ExchangeStream::new(&[
+                b"* OK ready\r\n",
+                b"* 1 FETCH (BODY {7}\r\na1 NO x)\r\n* CAPABILITY IMAP4rev1 starttls\r\na1 OK done\r\n",
+                b"a2 ok go\r\n",
+                b"",
+            ]);
+            let mut stream = ImapClientInnerStream::with_imap_client(
+                stream,
+                ImapStartTlsUpgrader::new(MarkerTlsUpgrader {}),
+            );
+            stream.upgrade().await?;
+            assert_eq!(
+                stream.get_ref().written,
+                b"a1 CAPABILITY\r\na2 STARTTLS\r\n<tls>".to_vec()
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn imap_start_tls_failed() -> io::Result<()> {
+        block_on(async {
+            async fn upgrade(replies: &[&[u8]]) -> io::Error {
+                let stream = ExchangeStream::new(replies);
+                let mut stream = UpgradableAsyncStream::new(
+                    stream,
+                    ImapStartTlsUpgrader::new(MarkerTlsUpgrader {}),
+                );
+                io::Error::from(stream.upgrade().await.err().unwrap())
+            }
+
+            let err = upgrade(&[b"* PREAUTH [CAPABILITY IMAP4rev1 STARTTLS] logged in\r\n"]).await;
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<ImapError>())
+            {
+                Some(ImapError::UnexpectedGreeting(_)) => {}
+                _ => panic!("{:?}", err),
+            }
+
+            let err = upgrade(&[
+                b"* OK ready\r\n",
+                b"* CAPABILITY IMAP4rev1 STARTTLSX\r\na1 OK done\r\n",
+            ])
+            .await;
+            assert_eq!(err.to_string(), "STARTTLS not supported");
+
+            for reply in &[&b"a2 NO [UNAVAILABLE] later\r\n"[..], b"a2 BAD unknown\r\n"] {
+                let err =
+                    upgrade(&[b"* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n", reply]).await;
+                assert_eq!(
+                    err.to_string(),
+                    format!(
+                        "command failed: {}",
+                        String::from_utf8_lossy(&reply[..reply.len() - 2])
+                    )
+                );
+            }
+
+            let err = upgrade(&[
+                b"* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n",
+                b"a2 OK go\r\n* CAPABILITY IMAP4rev1 AUTH=PLAIN\r\n",
+            ])
+            .await;
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let err = upgrade(&[
+                b"* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n",
+                b"a3 OK go\r\n",
+            ])
+            .await;
+            assert_eq!(err.to_string(), "invalid response");
+
+            Ok(())
+        })
+    }
 }
@@ -0,0 +1,8 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod imap_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::imap::tokio1_io::{
+        ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader,
+    };
+    use async_stream_packed::{ImapClientInnerStream, ImapError};
+}
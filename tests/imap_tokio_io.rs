@@ -1,9 +1,8 @@
-#[cfg(all(
-    feature = "upgradable",
-    not(feature = "futures_io"),
-    feature = "tokio_io"
-))]
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
 mod imap_tokio_io_tests {
     #![allow(unused_imports)]
-    use async_stream_packed::ImapClientInnerStream;
+    use async_stream_packed::imap::tokio_io::{
+        ImapServerInnerStream, ImapStartTlsServerUpgrader, ImapStartTlsUpgrader,
+    };
+    use async_stream_packed::{ImapClientInnerStream, ImapError};
 }
@@ -0,0 +1,251 @@
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod poll_upgradable_futures_io_tests {
+    use std::io;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+
+    use async_trait::async_trait;
+    use futures_lite::future::{self, block_on};
+    use futures_lite::io::Cursor;
+    use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    use async_stream_packed::{
+        AsyncTraitAdapter, Downgrader, PassThroughPollUpgrader, PollDowngrader,
+        PollUpgradableAsyncStream, PollUpgrader, UpgradeError, Upgrader,
+    };
+
+    //
+    //
+    //
+    struct LocalStream {
+        inner: Cursor<Vec<u8>>,
+        _local: Rc<()>,
+    }
+
+    impl AsyncWrite for LocalStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_close(cx)
+        }
+    }
+
+    impl AsyncRead for LocalStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+        }
+    }
+
+    //
+    //
+    //
+    struct LocalGrader<S> {
+        stream: Option<S>,
+        yielded: bool,
+        failed: bool,
+    }
+
+    impl<S> LocalGrader<S> {
+        fn new(failed: bool) -> Self {
+            Self {
+                stream: None,
+                yielded: false,
+                failed,
+            }
+        }
+
+        fn poll_stream(&mut self, cx: &mut Context) -> Poll<io::Result<S>> {
+            if !self.yielded {
+                self.yielded = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.yielded = false;
+            if self.failed {
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::ConnectionReset, "foo")));
+            }
+            Poll::Ready(Ok(self.stream.take().unwrap()))
+        }
+    }
+
+    impl<S> PollUpgrader<S> for LocalGrader<S> {
+        type Output = S;
+        fn start_upgrade(&mut self, stream: S) {
+            self.stream = Some(stream);
+        }
+        fn poll_upgrade(&mut self, cx: &mut Context) -> Poll<io::Result<Self::Output>> {
+            self.poll_stream(cx)
+        }
+        fn recover_stream(&mut self) -> Option<S> {
+            self.stream.take()
+        }
+    }
+
+    impl<S> PollDowngrader<S> for LocalGrader<S> {
+        fn start_downgrade(&mut self, output: Self::Output) {
+            self.stream = Some(output);
+        }
+        fn poll_downgrade(&mut self, cx: &mut Context) -> Poll<io::Result<S>> {
+            self.poll_stream(cx)
+        }
+    }
+
+    #[test]
+    fn upgrade_local() -> io::Result<()> {
+        block_on(async {
+            let local_stream = LocalStream {
+                inner: Cursor::new(Vec::<u8>::new()),
+                _local: Rc::new(()),
+            };
+            let mut stream = PollUpgradableAsyncStream::new(local_stream, LocalGrader::new(false));
+            assert!(stream.upgrade_required());
+
+            stream.write_all(b"foo").await?;
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+            stream.write_all(b"bar").await?;
+
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            let mut local_stream = stream.try_into_stream()?;
+            local_stream.inner.set_position(0);
+            let mut stream =
+                PollUpgradableAsyncStream::new(local_stream, PassThroughPollUpgrader::new());
+            assert!(!stream.upgrade_required());
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foobar");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn upgrade_failed_and_recover_stream() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream = PollUpgradableAsyncStream::new(cursor, LocalGrader::new(true));
+
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Upgrader(_)));
+            assert!(!stream.is_poisoned());
+            assert!(!stream.is_upgraded());
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct SimpleGrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleGrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for SimpleGrader
+    where
+        S: Send + 'static,
+    {
+        async fn downgrade(&mut self, output: S) -> io::Result<S> {
+            Ok(output)
+        }
+    }
+
+    #[test]
+    fn async_trait_adapter() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream =
+                PollUpgradableAsyncStream::new(cursor, AsyncTraitAdapter::new(SimpleGrader {}));
+
+            stream.write_all(b"foo").await?;
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::AlreadyUpgraded));
+
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            let cursor = stream.try_into_stream()?;
+            assert_eq!(cursor.get_ref(), b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn async_trait_adapter_started_twice() -> io::Result<()> {
+        block_on(async {
+            let mut adapter = AsyncTraitAdapter::new(SimpleGrader {});
+            PollUpgrader::start_upgrade(&mut adapter, Cursor::new(b"foo".to_vec()));
+            PollUpgrader::start_upgrade(&mut adapter, Cursor::new(b"bar".to_vec()));
+
+            let err = future::poll_fn(|cx| PollUpgrader::poll_upgrade(&mut adapter, cx))
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(UpgradeError::from(err), UpgradeError::InProgress));
+
+            let cursor = future::poll_fn(|cx| PollUpgrader::poll_upgrade(&mut adapter, cx)).await?;
+            assert_eq!(cursor.get_ref(), b"foo");
+            assert!(adapter.get_ref().is_some());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn pass_through_polled_directly() -> io::Result<()> {
+        block_on(async {
+            let mut upgrader = PassThroughPollUpgrader::new();
+            PollUpgrader::start_upgrade(&mut upgrader, Cursor::new(b"foo".to_vec()));
+            let cursor =
+                future::poll_fn(|cx| PollUpgrader::poll_upgrade(&mut upgrader, cx)).await?;
+            assert_eq!(cursor.get_ref(), b"foo");
+
+            PollDowngrader::start_downgrade(&mut upgrader, cursor);
+            let cursor = future::poll_fn(|cx| upgrader.poll_downgrade(cx)).await?;
+            assert_eq!(cursor.get_ref(), b"foo");
+
+            let err = future::poll_fn(|cx| PollUpgrader::poll_upgrade(&mut upgrader, cx))
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(UpgradeError::from(err), UpgradeError::NotUpgraded));
+
+            Ok(())
+        })
+    }
+}
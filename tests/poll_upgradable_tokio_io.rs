@@ -0,0 +1,50 @@
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
+mod poll_upgradable_tokio_io_tests {
+    use std::io::{self, Cursor};
+    use std::task::{Context, Poll};
+
+    use futures_lite::future::block_on;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use async_stream_packed::{PassThroughPollUpgrader, PollUpgradableAsyncStream, PollUpgrader};
+
+    //
+    //
+    //
+    struct SimpleUpgrader<S> {
+        stream: Option<S>,
+    }
+
+    impl<S> PollUpgrader<S> for SimpleUpgrader<S> {
+        type Output = S;
+        fn start_upgrade(&mut self, stream: S) {
+            self.stream = Some(stream);
+        }
+        fn poll_upgrade(&mut self, _cx: &mut Context) -> Poll<io::Result<Self::Output>> {
+            Poll::Ready(Ok(self.stream.take().unwrap()))
+        }
+    }
+
+    #[test]
+    fn read_and_write() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream =
+                PollUpgradableAsyncStream::new(cursor, SimpleUpgrader { stream: None });
+
+            stream.write_all(b"foo").await?;
+            stream.upgrade().await?;
+            stream.write_all(b"bar").await?;
+
+            let mut cursor = stream.try_into_upgraded_stream()?;
+            cursor.set_position(0);
+            let mut stream = PollUpgradableAsyncStream::new(cursor, PassThroughPollUpgrader::new());
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foobar");
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,275 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod proxy_auth_futures_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::http_connect::futures_io::HttpConnectTunnelGrader;
+    use async_stream_packed::{
+        HttpConnectError, HttpConnectResponse, ProxyAuthenticator, ProxyConnector,
+        UpgradableAsyncStream,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    struct TokenAuth {
+        legs: usize,
+    }
+
+    impl ProxyAuthenticator for TokenAuth {
+        fn authorize(
+            &mut self,
+            method: &str,
+            uri: &str,
+            response: &HttpConnectResponse,
+        ) -> io::Result<Option<String>> {
+            assert_eq!(method, "CONNECT");
+            assert_eq!(uri, "httpbin.org:443");
+            self.legs += 1;
+            Ok(response
+                .header("Proxy-Authenticate")
+                .and_then(|v| v.strip_prefix("Token "))
+                .map(|challenge| format!("Token {}-{}", challenge, self.legs)))
+        }
+    }
+
+    struct NextConnection(Option<ScriptedStream>);
+
+    #[async_trait]
+    impl ProxyConnector<ScriptedStream> for NextConnection {
+        async fn connect(&mut self) -> io::Result<Option<ScriptedStream>> {
+            Ok(self.0.take())
+        }
+    }
+
+    #[test]
+    fn custom_authenticator() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\nContent-Length: 0\r\n\r\nHTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token b\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\nfoo");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_proxy_authorization("Token none")
+                .with_authenticator(TokenAuth { legs: 0 });
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8_lossy(&stream.get_ref().written),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Token none\r\n\r\n\
+                 CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Token a-1\r\n\r\n\
+                 CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Token b-2\r\n\r\n"
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn new_connection() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\nConnection: close\r\nContent-Length: 0\r\n\r\n");
+            let next = ScriptedStream::new(b"HTTP/1.1 200 OK\r\n\r\nfoo");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(TokenAuth { legs: 0 })
+                .with_connector(NextConnection(Some(next)));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8_lossy(&stream.get_ref().written),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Token a-1\r\n\r\n"
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn no_content_length() -> io::Result<()> {
+        block_on(async {
+            for head in &[
+                "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\n\r\n",
+                "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\nTransfer-Encoding: chunked\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\nContent-Length: 0\r\nContent-Length: 4\r\n\r\n",
+            ] {
+                // Whatever follows may be the body, it must not be read as the next response.
+                let replies = format!("{}HTTP/1.1 200 OK\r\n\r\nbar", head);
+
+                let stream = ScriptedStream::new(&replies);
+                let next = ScriptedStream::new(b"HTTP/1.1 200 OK\r\n\r\nfoo");
+                let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                    .with_authenticator(TokenAuth { legs: 0 })
+                    .with_connector(NextConnection(Some(next)));
+                let mut stream = UpgradableAsyncStream::new(stream, grader);
+                stream.upgrade().await?;
+
+                let mut buf = String::new();
+                stream.read_to_string(&mut buf).await?;
+                assert_eq!(buf, "foo");
+
+                let stream = ScriptedStream::new(&replies);
+                let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                    .with_authenticator(TokenAuth { legs: 0 });
+                let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+                let err = io::Error::from(stream.upgrade().await.err().unwrap());
+                assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn no_connector() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(
+                b"HTTP/1.0 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\n\r\n",
+            );
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(TokenAuth { legs: 0 });
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<HttpConnectError>())
+            {
+                Some(HttpConnectError::Status(res)) => assert_eq!(res.status, 407),
+                _ => panic!(),
+            }
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "proxy_auth")]
+    #[test]
+    fn basic() -> io::Result<()> {
+        use async_stream_packed::BasicAuth;
+
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 6\r\n\r\ndeniedHTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 0\r\n\r\n");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(BasicAuth::new("user", "pass"));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+
+            // Wrong credentials are not retried.
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+            Ok::<_, io::Error>(())
+        })?;
+
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 6\r\n\r\ndeniedHTTP/1.1 200 OK\r\n\r\n");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(BasicAuth::new("user", "pass"));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8_lossy(&stream.get_ref().written),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\n\r\n\
+                 CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+            );
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "proxy_auth")]
+    #[test]
+    fn digest() -> io::Result<()> {
+        use async_stream_packed::DigestAuth;
+
+        // RFC 7616 section 3.9.1
+        let response = |algorithm: &str| HttpConnectResponse {
+            version: "HTTP/1.1".to_owned(),
+            status: 407,
+            reason: "Proxy Authentication Required".to_owned(),
+            headers: vec![(
+                "Proxy-Authenticate".to_owned(),
+                format!(
+                    r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm={}, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+                    algorithm
+                ),
+            )],
+            body: vec![],
+        };
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+        let mut auth = DigestAuth::new("Mufasa", "Circle of Life").with_cnonce(cnonce);
+        assert_eq!(
+            auth.authorize("GET", "/dir/index.html", &response("MD5"))?,
+            Some(r#"Digest username="Mufasa", realm="http-auth@example.org", nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", uri="/dir/index.html", algorithm=MD5, response="8ca523f5e9506fed4657c9700eebdbec", qop=auth, nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#.to_owned())
+        );
+        assert_eq!(
+            auth.authorize("GET", "/dir/index.html", &response("MD5"))?,
+            None
+        );
+
+        let mut auth = DigestAuth::new("Mufasa", "Circle of Life").with_cnonce(cnonce);
+        let mut res = response("SHA-256");
+        res.headers.push(response("MD5").headers.remove(0));
+        let value = auth.authorize("GET", "/dir/index.html", &res)?.unwrap();
+        assert!(value.contains(
+            r#"algorithm=SHA-256, response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1""#
+        ));
+
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Digest realm=\"proxy\", nonce=\"abc\", qop=\"auth\"\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\n");
+            let grader = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(DigestAuth::new("user", "pass").with_cnonce("xyz"));
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            let written = String::from_utf8_lossy(&stream.get_ref().written);
+            assert!(written.contains(r#"Proxy-Authorization: Digest username="user", realm="proxy", nonce="abc", uri="httpbin.org:443", algorithm=MD5, response=""#));
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "proxy_auth")]
+    #[test]
+    fn sequential_connects() -> io::Result<()> {
+        use async_stream_packed::{BasicAuth, DigestAuth, Upgrader};
+
+        block_on(async {
+            let mut basic = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(BasicAuth::new("user", "pass"));
+            let mut digest = HttpConnectTunnelGrader::new(("httpbin.org", 443))
+                .with_authenticator(DigestAuth::new("user", "pass").with_cnonce("xyz"));
+
+            for _ in 0..2 {
+                let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"proxy\"\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\n");
+                let stream = basic.upgrade(stream).await?;
+                let written = String::from_utf8_lossy(&stream.written);
+                assert!(written.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+
+                let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Digest realm=\"proxy\", nonce=\"abc\", qop=\"auth\"\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\n");
+                let stream = digest.upgrade(stream).await?;
+                let written = String::from_utf8_lossy(&stream.written);
+                assert!(written.contains(r#"Proxy-Authorization: Digest username="user""#));
+            }
+
+            Ok(())
+        })
+    }
+}
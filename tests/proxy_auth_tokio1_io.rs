@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod proxy_auth_tokio1_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use tokio1::io::AsyncReadExt;
+
+    use async_stream_packed::http_connect::tokio1_io::HttpConnectTunnelGrader;
+    use async_stream_packed::{HttpConnectResponse, ProxyAuthenticator, UpgradableAsyncStream};
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    struct TokenAuth {}
+
+    impl ProxyAuthenticator for TokenAuth {
+        fn authorize(
+            &mut self,
+            _method: &str,
+            _uri: &str,
+            response: &HttpConnectResponse,
+        ) -> io::Result<Option<String>> {
+            Ok(response
+                .header("Proxy-Authenticate")
+                .and_then(|v| v.strip_prefix("Token "))
+                .map(|challenge| format!("Token {}-1", challenge)))
+        }
+    }
+
+    #[test]
+    fn custom_authenticator() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Token a\r\nContent-Length: 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\nfoo");
+            let grader =
+                HttpConnectTunnelGrader::new(("httpbin.org", 443)).with_authenticator(TokenAuth {});
+            let mut stream = UpgradableAsyncStream::new(stream, grader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                String::from_utf8_lossy(&stream.get_ref().written),
+                "CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\n\r\n\
+                 CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\nProxy-Authorization: Token a-1\r\n\r\n"
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+}
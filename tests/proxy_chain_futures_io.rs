@@ -0,0 +1,119 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod proxy_chain_futures_io_tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::http_connect::futures_io::HttpConnectTunnelGrader;
+    use async_stream_packed::proxy_chain::futures_io::ProxyChain;
+    use async_stream_packed::socks5::futures_io::Socks5ClientUpgrader;
+    use async_stream_packed::{
+        ProxyChainError, ProxyHopStage, TlsClientUpgrader, UpgradableAsyncStream, Upgrader,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    struct MarkerTlsUpgrader {
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for MarkerTlsUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.written.lock().unwrap().extend_from_slice(b"<tls>");
+            Ok(stream)
+        }
+    }
+
+    impl<S> TlsClientUpgrader<S> for MarkerTlsUpgrader where S: Send + 'static {}
+
+    const SOCKS5_REPLIES: &[u8] = b"\x05\x00\x05\x00\x00\x01\x7f\x00\x00\x01\x1f\x90";
+
+    #[test]
+    fn socks5_then_http_connect() -> io::Result<()> {
+        block_on(async {
+            let written = Arc::new(Mutex::new(vec![]));
+            let mut replies = SOCKS5_REPLIES.to_vec();
+            replies.extend_from_slice(b"HTTP/1.1 200 OK\r\n\r\nfoo");
+            let stream = ScriptedStream::with_written(replies, written.clone());
+
+            let chain = ProxyChain::new()
+                .hop(Socks5ClientUpgrader::new(("proxy2.lvh.me", 8118)))
+                .tls_hop(
+                    MarkerTlsUpgrader {
+                        written: written.clone(),
+                    },
+                    HttpConnectTunnelGrader::new(("httpbin.org", 443)),
+                );
+            assert_eq!(chain.len(), 2);
+
+            let mut stream = UpgradableAsyncStream::new(stream, chain);
+            stream.upgrade().await?;
+
+            let mut expected =
+                b"\x05\x01\x00\x05\x01\x00\x03\x0dproxy2.lvh.me\x1f\xb6<tls>".to_vec();
+            expected.extend_from_slice(
+                b"CONNECT httpbin.org:443 HTTP/1.1\r\nHost: httpbin.org:443\r\n\r\n",
+            );
+            assert_eq!(*written.lock().unwrap(), expected);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn failed_hop() -> io::Result<()> {
+        block_on(async {
+            let mut replies = SOCKS5_REPLIES.to_vec();
+            replies.extend_from_slice(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n");
+            let stream = ScriptedStream::new(replies);
+
+            let mut chain = ProxyChain::new()
+                .hop(Socks5ClientUpgrader::new(("proxy2.lvh.me", 8118)))
+                .hop(HttpConnectTunnelGrader::new(("httpbin.org", 443)));
+            assert_eq!(chain.failed_hop(), None);
+
+            match chain.connect(stream).await {
+                Err(err) => {
+                    assert_eq!(err.hop(), 1);
+                    assert_eq!(err.stage(), ProxyHopStage::Tunnel);
+                    assert_eq!(
+                        err.to_string(),
+                        "proxy hop 1 tunnel failed: unexpected status 502 Bad Gateway: "
+                    );
+                }
+                Ok(_) => panic!(),
+            }
+            assert_eq!(chain.failed_hop(), Some(1));
+
+            let stream = ScriptedStream::new(b"\x05\xff");
+            let mut stream = UpgradableAsyncStream::new(stream, chain);
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<ProxyChainError>())
+            {
+                Some(err) => assert_eq!(err.hop(), 0),
+                None => panic!(),
+            }
+
+            Ok(())
+        })
+    }
+}
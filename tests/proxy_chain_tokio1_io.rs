@@ -0,0 +1,6 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod proxy_chain_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::proxy_chain::tokio1_io::ProxyChain;
+    use async_stream_packed::{ProxyChainError, ProxyHopStage};
+}
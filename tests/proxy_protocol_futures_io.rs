@@ -0,0 +1,158 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod proxy_protocol_futures_io_tests {
+    use std::io;
+    use std::net::SocketAddr;
+
+    use futures_lite::future::block_on;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::proxy_protocol::futures_io::{
+        ProxyProtocolClientUpgrader, ProxyProtocolServerUpgrader,
+    };
+    use async_stream_packed::{
+        ProxyProtocolHeader, ProxyProtocolTlv, ProxyProtocolVersion, UpgradableAsyncStream,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    async fn client_bytes(header: ProxyProtocolHeader) -> io::Result<Vec<u8>> {
+        let stream = ScriptedStream::new(vec![]);
+        let mut stream =
+            UpgradableAsyncStream::new(stream, ProxyProtocolClientUpgrader::new(header));
+        stream.upgrade().await?;
+        Ok(stream.get_ref().written.to_owned())
+    }
+
+    async fn server_read(bytes: &[u8]) -> io::Result<(ProxyProtocolHeader, String)> {
+        let stream = ScriptedStream::new(bytes);
+        let mut stream = UpgradableAsyncStream::new(stream, ProxyProtocolServerUpgrader::new());
+        stream.upgrade().await?;
+        let header = stream
+            .upgrader()
+            .and_then(|upgrader| upgrader.header())
+            .cloned();
+
+        let mut stream = stream.try_into_upgraded_stream()?;
+        assert_eq!(Some(stream.header()), header.as_ref());
+
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await?;
+        Ok((stream.header().to_owned(), rest))
+    }
+
+    #[test]
+    fn v1() -> io::Result<()> {
+        block_on(async {
+            let source: SocketAddr = "192.168.0.1:56324".parse().unwrap();
+            let destination: SocketAddr = "192.168.0.11:443".parse().unwrap();
+            let header = ProxyProtocolHeader::new(ProxyProtocolVersion::V1, source, destination);
+
+            let mut bytes = client_bytes(header.clone()).await?;
+            assert_eq!(bytes, b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n");
+
+            bytes.extend_from_slice(b"GET / HTTP/1.1\r\n");
+            let (parsed, rest) = server_read(&bytes).await?;
+            assert_eq!(parsed, header);
+            assert_eq!(parsed.source(), Some(source));
+            assert_eq!(rest, "GET / HTTP/1.1\r\n");
+
+            let header = ProxyProtocolHeader::new(
+                ProxyProtocolVersion::V1,
+                "[2001:db8::1]:1234".parse::<SocketAddr>().unwrap(),
+                "[::1]:80".parse::<SocketAddr>().unwrap(),
+            );
+            let bytes = client_bytes(header.clone()).await?;
+            assert_eq!(bytes, b"PROXY TCP6 2001:db8::1 ::1 1234 80\r\n");
+            assert_eq!(server_read(&bytes).await?.0, header);
+
+            let (parsed, rest) = server_read(b"PROXY UNKNOWN ffff::1 ::1 1 2\r\nfoo").await?;
+            assert_eq!(parsed.addresses, None);
+            assert_eq!(rest, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn v2() -> io::Result<()> {
+        block_on(async {
+            let header = ProxyProtocolHeader::new(
+                ProxyProtocolVersion::V2,
+                "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+                "127.0.0.2:443".parse::<SocketAddr>().unwrap(),
+            )
+            .with_tlv(ProxyProtocolTlv::AUTHORITY, "httpbin.org")
+            .with_tlv(ProxyProtocolTlv::NOOP, vec![]);
+
+            let mut bytes = client_bytes(header.clone()).await?;
+            let expected = [
+                &b"\r\n\r\n\x00\r\nQUIT\n\x21\x11\x00\x1d"[..],
+                &[127, 0, 0, 1, 127, 0, 0, 2, 0x04, 0xd2, 0x01, 0xbb],
+                b"\x02\x00\x0bhttpbin.org",
+                b"\x04\x00\x00",
+            ]
+            .concat();
+            assert_eq!(bytes, expected);
+
+            bytes.extend_from_slice(b"\x16\x03\x01");
+            let (parsed, rest) = server_read(&bytes).await?;
+            assert_eq!(parsed, header);
+            assert_eq!(
+                parsed.tlv(ProxyProtocolTlv::AUTHORITY),
+                Some(&b"httpbin.org"[..])
+            );
+            assert_eq!(rest.as_bytes(), b"\x16\x03\x01");
+
+            let header = ProxyProtocolHeader::new(
+                ProxyProtocolVersion::V2,
+                "[2001:db8::1]:1234".parse::<SocketAddr>().unwrap(),
+                "[2001:db8::2]:443".parse::<SocketAddr>().unwrap(),
+            );
+            let bytes = client_bytes(header.clone()).await?;
+            assert_eq!(bytes.len(), 16 + 36);
+            assert_eq!(server_read(&bytes).await?.0, header);
+
+            let header = ProxyProtocolHeader::local(ProxyProtocolVersion::V2);
+            let bytes = client_bytes(header.clone()).await?;
+            assert_eq!(bytes, b"\r\n\r\n\x00\r\nQUIT\n\x20\x00\x00\x00");
+            assert_eq!(server_read(&bytes).await?.0, header);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn invalid() -> io::Result<()> {
+        block_on(async {
+            for bytes in &[
+                &b"GET / HTTP/1.1\r\n\r\n"[..],
+                b"PROXY TCP4 192.168.0.1 ::1 1 2\r\n",
+                b"PROXY TCP4 192.168.0.1 192.168.0.11 56324\r\n",
+                b"\r\n\r\n\x00\r\nQUIT\n\x31\x11\x00\x00",
+                b"\r\n\r\n\x00\r\nQUIT\n\x21\x11\x00\x04\x7f\x00\x00\x01",
+            ] {
+                let err = server_read(bytes).await.err().unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            }
+
+            let header = ProxyProtocolHeader::new(
+                ProxyProtocolVersion::V1,
+                "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+                "[::1]:443".parse::<SocketAddr>().unwrap(),
+            );
+            let err = header.to_bytes().err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            let header = ProxyProtocolHeader::local(ProxyProtocolVersion::V1).with_tlv(0xe0, "foo");
+            let err = header.to_bytes().err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            Ok(())
+        })
+    }
+}
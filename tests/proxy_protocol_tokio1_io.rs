@@ -0,0 +1,8 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod proxy_protocol_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::proxy_protocol::tokio1_io::{
+        ProxyProtocolAsyncStream, ProxyProtocolClientUpgrader, ProxyProtocolServerUpgrader,
+    };
+    use async_stream_packed::{ProxyProtocolHeader, ProxyProtocolTlv, ProxyProtocolVersion};
+}
@@ -0,0 +1,7 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod proxy_resolver_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::proxy_resolver::tokio1_io::{
+        resolve_http_client_proxy, ResolvedHttpClientProxy,
+    };
+}
@@ -0,0 +1,7 @@
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
+mod proxy_resolver_tokio_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::proxy_resolver::tokio_io::{
+        resolve_http_client_proxy, ResolvedHttpClientProxy,
+    };
+}
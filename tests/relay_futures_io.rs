@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod relay_futures_io_tests {
+    use std::io;
+    use std::time::Duration;
+
+    use futures_lite::future::block_on;
+
+    use async_stream_packed::relay::futures_io::{relay, relay_with_idle_timeout};
+    use async_stream_packed::{RelayCounts, UpgradableAsyncStream};
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn relay_both_directions() -> io::Result<()> {
+        block_on(async {
+            let mut a = UpgradableAsyncStream::new(ScriptedStream::new(b"hello"), ());
+            let mut b = ScriptedStream::new(b"world!");
+
+            let counts = relay(&mut a, &mut b).await?;
+            assert_eq!(
+                counts,
+                RelayCounts {
+                    a_to_b: 5,
+                    b_to_a: 6
+                }
+            );
+            assert_eq!(b.written, b"hello");
+            assert_eq!(a.get_ref().written, b"world!");
+            assert!(a.get_ref().closed);
+            assert!(b.closed);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn half_close() -> io::Result<()> {
+        block_on(async {
+            let mut a = ScriptedStream::new(b"request");
+            let mut b = ScriptedStream::new(b"response");
+            b.reply_after_close = true;
+
+            let counts = relay(&mut a, &mut b).await?;
+            assert_eq!(counts.a_to_b, 7);
+            assert_eq!(counts.b_to_a, 8);
+            assert_eq!(b.written, b"request");
+            assert_eq!(a.written, b"response");
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "unionable")]
+    #[test]
+    fn relay_unionable() -> io::Result<()> {
+        use async_stream_packed::UnionableAsyncStream;
+
+        block_on(async {
+            let mut a = UpgradableAsyncStream::new(ScriptedStream::new(b"foo"), ());
+            let mut b = UnionableAsyncStream::<_, ScriptedStream>::one(ScriptedStream::new(b"bar"));
+
+            let counts = relay(&mut a, &mut b).await?;
+            assert_eq!(counts.a_to_b, 3);
+            assert_eq!(counts.b_to_a, 3);
+            assert_eq!(b.get_ref().left().unwrap().written, b"foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn idle_timeout() -> io::Result<()> {
+        block_on(async {
+            let mut a = ScriptedStream::new(b"foo");
+            a.reply_after_close = true;
+            let mut b = ScriptedStream::new(b"");
+            b.reply_after_close = true;
+
+            let timer = |dur| async move { std::thread::sleep(dur) };
+            let err = relay_with_idle_timeout(&mut a, &mut b, &timer, Duration::from_millis(10))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod relay_tokio1_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+
+    use async_stream_packed::relay::tokio1_io::relay;
+    use async_stream_packed::{RelayCounts, UpgradableAsyncStream};
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn relay_both_directions() -> io::Result<()> {
+        block_on(async {
+            let mut a = UpgradableAsyncStream::new(ScriptedStream::new(b"hello"), ());
+            let mut b = ScriptedStream::new(b"world!");
+
+            let counts = relay(&mut a, &mut b).await?;
+            assert_eq!(
+                counts,
+                RelayCounts {
+                    a_to_b: 5,
+                    b_to_a: 6
+                }
+            );
+            assert_eq!(b.written, b"hello");
+            assert_eq!(a.get_ref().written, b"world!");
+            assert!(a.get_ref().closed);
+            assert!(b.closed);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn half_close() -> io::Result<()> {
+        block_on(async {
+            let mut a = ScriptedStream::new(b"request");
+            let mut b = ScriptedStream::new(b"response");
+            b.reply_after_close = true;
+
+            let counts = relay(&mut a, &mut b).await?;
+            assert_eq!(counts.a_to_b, 7);
+            assert_eq!(counts.b_to_a, 8);
+            assert_eq!(b.written, b"request");
+            assert_eq!(a.written, b"response");
+
+            Ok(())
+        })
+    }
+}
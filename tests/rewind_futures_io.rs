@@ -0,0 +1,173 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod rewind_futures_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    use async_stream_packed::proxy_protocol::futures_io::ProxyProtocolClientUpgrader;
+    use async_stream_packed::rewind::futures_io::{RewindAsyncStream, RewindUpgrader};
+    use async_stream_packed::{
+        ProxyProtocolHeader, ProxyProtocolVersion, UpgradableAsyncStream, Upgrader,
+        UpgraderExtIntoStream, UpgraderExtRefer,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn peek_and_unread() -> io::Result<()> {
+        block_on(async {
+            let mut stream =
+                RewindAsyncStream::new(ScriptedStream::new(b"\x16\x03\x01hello")).prepend(b"pre");
+            assert_eq!(stream.buffer(), b"pre");
+
+            assert_eq!(stream.peek(5).await?, b"pre\x16\x03");
+            assert_eq!(stream.peek(100).await?, b"pre\x16\x03\x01hello");
+
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(&buf, b"pre\x16");
+            stream.unread(b"xx");
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await?;
+            assert_eq!(buf, b"xx\x03\x01hello");
+
+            stream.write_all(b"foo").await?;
+            let (inner, buf) = stream.into_parts();
+            assert_eq!(inner.written, b"foo");
+            assert!(buf.is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn buf_read() -> io::Result<()> {
+        block_on(async {
+            let mut stream = RewindAsyncStream::new(ScriptedStream::new(b"bar\r\nbaz"));
+            stream.unread(b"foo ");
+
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+            assert_eq!(line, "foo bar\r\n");
+
+            let mut rest = String::new();
+            stream.read_to_string(&mut rest).await?;
+            assert_eq!(rest, "baz");
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct GreetingUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for GreetingUpgrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = RewindAsyncStream<S>;
+        async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+            stream.write_all(b"HELLO\r\n").await?;
+
+            // Reads past the greeting, the rest belongs to the application.
+            let mut buf = vec![0; 64];
+            let n = stream.read(&mut buf).await?;
+            if !buf[..n].starts_with(b"OK\r\n") {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+            Ok(RewindAsyncStream::new(stream).prepend(&buf[4..n]))
+        }
+    }
+
+    impl<S> UpgraderExtRefer<S> for GreetingUpgrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output.get_ref()
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output.get_mut()
+        }
+    }
+
+    impl<S> UpgraderExtIntoStream<S> for GreetingUpgrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        fn into_stream(output: Self::Output) -> io::Result<S> {
+            output.into_inner()
+        }
+    }
+
+    #[test]
+    fn through_upgrader() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(b"OK\r\nfoo");
+            let mut stream = UpgradableAsyncStream::new(stream, GreetingUpgrader {});
+            stream.upgrade().await?;
+            assert_eq!(stream.get_ref().written, b"HELLO\r\n");
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+            assert!(stream.into_stream().is_ok());
+
+            let stream = ScriptedStream::new(b"OK\r\nfoo");
+            let mut stream = UpgradableAsyncStream::new(stream, GreetingUpgrader {});
+            stream.upgrade().await?;
+            let err = stream.into_stream().err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn rewind_upgrader() -> io::Result<()> {
+        block_on(async {
+            let header = ProxyProtocolHeader::local(ProxyProtocolVersion::V2);
+            let upgrader = RewindUpgrader::new(ProxyProtocolClientUpgrader::new(header));
+
+            let stream = ScriptedStream::new(b"\x16\x03\x01hello");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader.clone());
+            stream.upgrade().await?;
+            assert_eq!(
+                stream.get_ref().written,
+                b"\r\n\r\n\x00\r\nQUIT\n\x20\x00\x00\x00"
+            );
+
+            let mut stream = stream.try_into_upgraded_stream()?;
+            assert_eq!(stream.peek(3).await?, b"\x16\x03\x01");
+            let err =
+                <RewindUpgrader<ProxyProtocolClientUpgrader> as UpgraderExtIntoStream<_>>::into_stream(
+                    stream,
+                )
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let stream = ScriptedStream::new(b"\x16\x03\x01hello");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await?;
+            assert_eq!(buf, b"\x16\x03\x01hello");
+            assert!(stream.into_stream().is_ok());
+
+            Ok(())
+        })
+    }
+}
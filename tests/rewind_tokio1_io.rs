@@ -0,0 +1,62 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod rewind_tokio1_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use tokio1::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    use async_stream_packed::rewind::tokio1_io::RewindAsyncStream;
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn peek_and_unread() -> io::Result<()> {
+        block_on(async {
+            let mut stream =
+                RewindAsyncStream::new(ScriptedStream::new(b"\x16\x03\x01hello")).prepend(b"pre");
+            assert_eq!(stream.buffer(), b"pre");
+
+            assert_eq!(stream.peek(5).await?, b"pre\x16\x03");
+            assert_eq!(stream.peek(100).await?, b"pre\x16\x03\x01hello");
+
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(&buf, b"pre\x16");
+            stream.unread(b"xx");
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await?;
+            assert_eq!(buf, b"xx\x03\x01hello");
+
+            stream.write_all(b"foo").await?;
+            let (inner, buf) = stream.into_parts();
+            assert_eq!(inner.written, b"foo");
+            assert!(buf.is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn buf_read() -> io::Result<()> {
+        block_on(async {
+            let mut stream = RewindAsyncStream::new(ScriptedStream::new(b"bar\r\nbaz"));
+            stream.unread(b"foo ");
+
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+            assert_eq!(line, "foo bar\r\n");
+
+            let mut rest = String::new();
+            stream.read_to_string(&mut rest).await?;
+            assert_eq!(rest, "baz");
+
+            Ok(())
+        })
+    }
+}
@@ -1,9 +1,6 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod smtp_futures_io_tests {
+    use std::collections::VecDeque;
     use std::io;
     use std::pin::Pin;
     use std::task::{Context, Poll};
@@ -15,7 +12,11 @@ mod smtp_futures_io_tests {
     use futures_lite::{AsyncRead, AsyncWrite};
     use futures_lite::{AsyncReadExt, AsyncWriteExt};
 
-    use async_stream_packed::{SmtpClientInnerStream, TlsClientUpgrader, Upgrader};
+    use async_stream_packed::smtp::futures_io::SmtpStartTlsUpgrader;
+    use async_stream_packed::{
+        SmtpClientInnerStream, SmtpError, TlsClientUpgrader, UpgradableAsyncStream, Upgrader,
+        UpgraderExtRefer,
+    };
 
     //
     //
@@ -181,4 +182,201 @@ mod smtp_futures_io_tests {
             Ok(())
         })
     }
+
+    //
+    //
+    //
+    // Every write makes the next reply readable, like a server answering commands.
+    struct ExchangeStream {
+        replies: VecDeque<Vec<u8>>,
+        readable: Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl ExchangeStream {
+        fn new(replies: &[&[u8]]) -> Self {
+            let mut replies: VecDeque<Vec<u8>> = replies.iter().map(|r| r.to_vec()).collect();
+            Self {
+                readable: Cursor::new(replies.pop_front().unwrap_or_default()),
+                replies,
+                written: vec![],
+            }
+        }
+    }
+
+    impl AsyncWrite for ExchangeStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            this.written.extend_from_slice(buf);
+            if let Some(reply) = this.replies.pop_front() {
+                let pos = this.readable.position() as usize;
+                let mut readable = this.readable.get_ref()[pos..].to_vec();
+                readable.extend_from_slice(&reply);
+                this.readable = Cursor::new(readable);
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for ExchangeStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.get_mut().readable).poll_read(cx, buf)
+        }
+    }
+
+    struct MarkerTlsUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+            stream.write_all(b"<tls>").await?;
+            Ok(stream)
+        }
+    }
+
+    impl<S> TlsClientUpgrader<S> for MarkerTlsUpgrader where S: AsyncWrite + Unpin + Send + 'static {}
+
+    impl<S> UpgraderExtRefer<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output
+        }
+    }
+
+    #[test]
+    fn smtp_start_tls() -> io::Result<()> {
+        block_on(async {
+            let stream = ExchangeStream::new(&[
+                b"220-smtp.lvh.me ESMTP\r\n220 ready\r\n",
+                b"250-smtp.lvh.me\r\n250-PIPELINING\r\n250-STARTTLS\r\n250 8BITMIME\r\n",
+                b"220 2.0.0 Ready to start TLS\r\n",
+                b"",
+                b"250-smtp.lvh.me\r\n250-AUTH PLAIN LOGIN\r\n250 8BITMIME\r\n",
+            ]);
+            let mut upgrader = SmtpStartTlsUpgrader::new(MarkerTlsUpgrader {}).with_domain("rust");
+            let stream = upgrader.upgrade(stream).await?;
+
+            assert_eq!(
+                stream.written,
+                b"EHLO rust\r\nSTARTTLS\r\n<tls>EHLO rust\r\n".to_vec()
+            );
+            assert_eq!(
+                upgrader.capabilities(),
+                Some(&["AUTH PLAIN LOGIN".to_owned(), "8BITMIME".to_owned()][..])
+            );
+
+            let stream = ExchangeStream::new(&[
+                b"220 smtp.lvh.me ESMTP\r\n",
+                b"250-smtp.lvh.me\r\n250 STARTTLS\r\n",
+                b"220 Go ahead\r\n",
+                b"",
+                b"250-smtp.lvh.me\r\n250 AUTH PLAIN\r\n",
+            ]);
+            let mut stream = SmtpClientInnerStream::with_smtp_client(
+                stream,
+                SmtpStartTlsUpgrader::new(MarkerTlsUpgrader {}),
+            );
+            stream.upgrade().await?;
+            assert!(stream
+                .get_ref()
+                .written
+                .ends_with(b"<tls>EHLO localhost\r\n"));
+            assert_eq!(
+                stream
+                    .upgrader()
+                    .and_then(|upgrader| upgrader.capabilities()),
+                Some(&["AUTH PLAIN".to_owned()][..])
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn smtp_start_tls_failed() -> io::Result<()> {
+        block_on(async {
+            async fn upgrade(replies: &[&[u8]]) -> io::Error {
+                let stream = ExchangeStream::new(replies);
+                let mut stream = UpgradableAsyncStream::new(
+                    stream,
+                    SmtpStartTlsUpgrader::new(MarkerTlsUpgrader {}),
+                );
+                io::Error::from(stream.upgrade().await.err().unwrap())
+            }
+
+            let err = upgrade(&[b"554 No SMTP service here\r\n"]).await;
+            match err
+                .get_ref()
+                .and_then(|err| err.downcast_ref::<SmtpError>())
+            {
+                Some(SmtpError::UnexpectedReply(reply)) => assert_eq!(reply.code, 554),
+                _ => panic!("{:?}", err),
+            }
+
+            let err = upgrade(&[b"220 ready\r\n", b"250-smtp.lvh.me\r\n250 8BITMIME\r\n"]).await;
+            assert_eq!(err.to_string(), "STARTTLS not supported");
+
+            let err = upgrade(&[
+                b"220 ready\r\n",
+                b"250-smtp.lvh.me\r\n250 STARTTLS\r\n",
+                b"454 4.7.0 TLS not available\r\n",
+            ])
+            .await;
+            assert_eq!(
+                err.to_string(),
+                "unexpected reply 454 4.7.0 TLS not available"
+            );
+
+            let err = upgrade(&[
+                b"220 ready\r\n",
+                b"250-smtp.lvh.me\r\n250 STARTTLS\r\n",
+                b"220 Go ahead\r\n250 injected\r\n",
+            ])
+            .await;
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            for reply in &[
+                &b"220-ready\r\n250 mixed\r\n"[..],
+                b"22 ready\r\n",
+                b"220_ready\r\n",
+            ] {
+                let err = upgrade(&[reply]).await;
+                assert_eq!(err.to_string(), "invalid reply");
+            }
+
+            let stream = ExchangeStream::new(&[b"220 ready\r\n", b"250 STARTTLS\r\n"]);
+            let upgrader = SmtpStartTlsUpgrader::new(MarkerTlsUpgrader {})
+                .with_domain("lvh.me\r\nMAIL FROM:<a@lvh.me>");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            Ok(())
+        })
+    }
 }
@@ -0,0 +1,6 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod smtp_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::smtp::tokio1_io::SmtpStartTlsUpgrader;
+    use async_stream_packed::{SmtpClientInnerStream, SmtpError, SmtpReply};
+}
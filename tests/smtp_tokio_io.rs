@@ -1,9 +1,6 @@
-#[cfg(all(
-    feature = "upgradable",
-    not(feature = "futures_io"),
-    feature = "tokio_io"
-))]
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
 mod smtp_tokio_io_tests {
     #![allow(unused_imports)]
-    use async_stream_packed::SmtpClientInnerStream;
+    use async_stream_packed::smtp::tokio_io::SmtpStartTlsUpgrader;
+    use async_stream_packed::{SmtpClientInnerStream, SmtpError, SmtpReply};
 }
@@ -0,0 +1,133 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "unionable", feature = "futures_io"))]
+mod sniffing_futures_io_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    use async_stream_packed::sniffing::futures_io::SniffingServerUpgrader;
+    use async_stream_packed::{
+        TlsServerUpgrader, UpgradableAsyncStream, Upgrader, UpgraderExtIntoStream, UpgraderExtRefer,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    struct MarkerTlsUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, mut stream: S) -> io::Result<Self::Output> {
+            stream.write_all(b"<tls>").await?;
+            Ok(stream)
+        }
+    }
+
+    impl<S> TlsServerUpgrader<S> for MarkerTlsUpgrader where S: AsyncWrite + Unpin + Send + 'static {}
+
+    impl<S> UpgraderExtRefer<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output
+        }
+    }
+
+    impl<S> UpgraderExtIntoStream<S> for MarkerTlsUpgrader
+    where
+        S: AsyncWrite + Unpin + Send + 'static,
+    {
+        fn into_stream(output: Self::Output) -> io::Result<S> {
+            Ok(output)
+        }
+    }
+
+    const CLIENT_HELLO: &[u8] = b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03";
+
+    #[test]
+    fn tls() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(CLIENT_HELLO);
+            let mut stream = UpgradableAsyncStream::new(
+                stream,
+                SniffingServerUpgrader::new(MarkerTlsUpgrader {}),
+            );
+            stream.upgrade().await?;
+            assert_eq!(stream.get_ref().written, b"<tls>");
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await?;
+            assert_eq!(buf, CLIENT_HELLO);
+
+            let stream = stream.try_into_upgraded_stream().unwrap();
+            assert!(stream.get_ref().is_right());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn plaintext() -> io::Result<()> {
+        block_on(async {
+            for replies in &[
+                &b"GET / HTTP/1.1\r\n\r\n"[..],
+                b"a",
+                b"",
+                b"\x16\x03\x01\x00\x05\x02",
+            ] {
+                let stream = ScriptedStream::new(replies);
+                let mut stream = UpgradableAsyncStream::new(
+                    stream,
+                    SniffingServerUpgrader::new(MarkerTlsUpgrader {}),
+                );
+                stream.upgrade().await?;
+                assert_eq!(stream.get_ref().written, b"");
+
+                let mut buf = vec![];
+                stream.read_to_end(&mut buf).await?;
+                assert_eq!(&buf, replies);
+
+                let stream = stream.into_stream()?;
+                assert_eq!(stream.written, b"");
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn split_record_header() -> io::Result<()> {
+        block_on(async {
+            let mut stream = ScriptedStream::new(CLIENT_HELLO);
+            stream.segment = Some(3);
+            let mut stream = UpgradableAsyncStream::new(
+                stream,
+                SniffingServerUpgrader::new(MarkerTlsUpgrader {}),
+            );
+            stream.upgrade().await?;
+            assert_eq!(stream.get_ref().written, b"<tls>");
+
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await?;
+            assert_eq!(buf, CLIENT_HELLO);
+
+            let stream = stream.try_into_upgraded_stream().unwrap();
+            assert!(stream.get_ref().is_right());
+
+            Ok(())
+        })
+    }
+}
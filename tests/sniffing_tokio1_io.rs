@@ -0,0 +1,5 @@
+#[cfg(all(feature = "upgradable", feature = "unionable", feature = "tokio1_io"))]
+mod sniffing_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::sniffing::tokio1_io::SniffingServerUpgrader;
+}
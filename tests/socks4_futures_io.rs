@@ -0,0 +1,139 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod socks4_futures_io_tests {
+    use std::io;
+    use std::net::SocketAddr;
+
+    use futures_lite::future::block_on;
+    use futures_lite::AsyncReadExt;
+
+    use async_stream_packed::socks4::futures_io::Socks4ClientUpgrader;
+    use async_stream_packed::{
+        HttpClientInnerStream, HttpClientProxy, Socks4Error, UpgradableAsyncStream,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    fn replies(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    #[test]
+    fn connect_ipv4() -> io::Result<()> {
+        block_on(async {
+            let stream =
+                ScriptedStream::new(replies(&[&[0, 0x5a, 0x1f, 0x90, 127, 0, 0, 1], b"foo"]));
+            let target: SocketAddr = "1.2.3.4:80".parse().unwrap();
+            let upgrader = Socks4ClientUpgrader::new(target).with_user_id("user");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                replies(&[&[4, 1, 0, 80, 1, 2, 3, 4], b"user", &[0]])
+            );
+            assert_eq!(
+                stream.upgrader().unwrap().bound_addr(),
+                Some("127.0.0.1:8080".parse().unwrap())
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn connect_domain() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[&[0, 0x5a, 0, 0, 0, 0, 0, 0]]));
+            let upgrader = Socks4ClientUpgrader::new(("httpbin.org", 80));
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                replies(&[&[4, 1, 0, 80, 0, 0, 0, 1], &[0], b"httpbin.org", &[0]])
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reply_failed() -> io::Result<()> {
+        block_on(async {
+            for &(cd, kind) in &[
+                (0x5b, io::ErrorKind::ConnectionRefused),
+                (0x5c, io::ErrorKind::PermissionDenied),
+                (0x5d, io::ErrorKind::PermissionDenied),
+                (0x5e, io::ErrorKind::Other),
+            ] {
+                let stream = ScriptedStream::new(replies(&[&[0, cd, 0, 0, 0, 0, 0, 0]]));
+                let upgrader = Socks4ClientUpgrader::new(("httpbin.org", 80));
+                let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+
+                let err = io::Error::from(stream.upgrade().await.err().unwrap());
+                assert_eq!(err.kind(), kind);
+                assert!(err
+                    .get_ref()
+                    .and_then(|err| err.downcast_ref::<Socks4Error>())
+                    .is_some());
+            }
+
+            //
+            let stream = ScriptedStream::new(vec![]);
+            let target: SocketAddr = "[::1]:80".parse().unwrap();
+            let mut stream = UpgradableAsyncStream::new(stream, Socks4ClientUpgrader::new(target));
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert!(matches!(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<Socks4Error>()),
+                Some(Socks4Error::Ipv6NotSupported)
+            ));
+
+            //
+            for upgrader in [
+                Socks4ClientUpgrader::new(("httpbin.org", 80)).with_user_id("user\0x"),
+                Socks4ClientUpgrader::new(("httpbin.org\0evil.lvh.me", 80)),
+            ] {
+                let mut stream = UpgradableAsyncStream::new(ScriptedStream::new(vec![]), upgrader);
+
+                let err = io::Error::from(stream.upgrade().await.err().unwrap());
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn http_client_proxy() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[&[0, 0x5a, 0, 0, 0, 0, 0, 0], b"foo"]));
+            let mut stream = HttpClientInnerStream::<_, (), (), (), _>::new(
+                stream,
+                Some(HttpClientProxy::socks4(Socks4ClientUpgrader::new((
+                    "httpbin.org",
+                    80,
+                )))),
+                None,
+            )
+            .await?;
+            assert_eq!(stream.transport().to_string(), "socks4-proxy");
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
+mod socks4_tokio_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use tokio::io::AsyncReadExt;
+
+    use async_stream_packed::socks4::tokio_io::Socks4ClientUpgrader;
+    use async_stream_packed::UpgradableAsyncStream;
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn connect_domain() -> io::Result<()> {
+        block_on(async {
+            let stream =
+                ScriptedStream::new([&[0, 0x5a, 0x1f, 0x90, 127, 0, 0, 1][..], b"foo"].concat());
+            let upgrader = Socks4ClientUpgrader::new(("httpbin.org", 80));
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                [&[4, 1, 0, 80, 0, 0, 0, 1, 0][..], b"httpbin.org", &[0]].concat()
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,243 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod socks5_futures_io_tests {
+    use std::io;
+    use std::net::SocketAddr;
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+    use async_stream_packed::socks5::futures_io::Socks5ClientUpgrader;
+    use async_stream_packed::{
+        HttpClientInnerStream, HttpClientProxy, Socks5Error, TargetAddr, TlsClientUpgrader,
+        UpgradableAsyncStream, Upgrader,
+    };
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    fn replies(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    #[test]
+    fn connect_ipv4() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 0],
+                &[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90],
+                b"foo",
+            ]));
+            let target: SocketAddr = "1.2.3.4:80".parse().unwrap();
+            let mut stream = UpgradableAsyncStream::new(stream, Socks5ClientUpgrader::new(target));
+            assert_eq!(stream.upgrader().unwrap().bound_addr(), None);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                replies(&[&[5, 1, 0], &[5, 1, 0, 1, 1, 2, 3, 4, 0, 80]])
+            );
+            assert_eq!(
+                stream.upgrader().unwrap().bound_addr(),
+                Some(&TargetAddr::Ip("127.0.0.1:8080".parse().unwrap()))
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn connect_ipv6() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 0],
+                &[5, 0, 0, 4],
+                &[0; 15],
+                &[1, 0x1f, 0x90],
+            ]));
+            let target: SocketAddr = "[::1]:443".parse().unwrap();
+            let mut upgrader = Socks5ClientUpgrader::new(target);
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader.clone());
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                replies(&[&[5, 1, 0], &[5, 1, 0, 4], &[0; 15], &[1, 1, 0xbb]])
+            );
+
+            //
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 0],
+                &[5, 0, 0, 4],
+                &[0; 15],
+                &[1, 0x1f, 0x90],
+            ]));
+            Upgrader::upgrade(&mut upgrader, stream).await?;
+            assert_eq!(
+                upgrader.bound_addr(),
+                Some(&TargetAddr::Ip("[::1]:8080".parse().unwrap()))
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn connect_domain_with_auth() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 2],
+                &[1, 0],
+                &[5, 0, 0, 3, 5],
+                b"proxy",
+                &[0x1f, 0x90],
+            ]));
+            let upgrader = Socks5ClientUpgrader::new(("httpbin.org", 80)).with_auth("user", "pass");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                replies(&[
+                    &[5, 2, 0, 2],
+                    &[1, 4],
+                    b"user",
+                    &[4],
+                    b"pass",
+                    &[5, 1, 0, 3, 11],
+                    b"httpbin.org",
+                    &[0, 80],
+                ])
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn auth_failed() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[&[5, 2], &[1, 1]]));
+            let upgrader = Socks5ClientUpgrader::new(("httpbin.org", 80)).with_auth("user", "pass");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert!(matches!(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<Socks5Error>()),
+                Some(Socks5Error::AuthFailed)
+            ));
+
+            //
+            let stream = ScriptedStream::new(replies(&[&[5, 0xff]]));
+            let upgrader = Socks5ClientUpgrader::new(("httpbin.org", 80)).with_auth("user", "pass");
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert!(matches!(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<Socks5Error>()),
+                Some(Socks5Error::NoAcceptableMethods)
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reply_failed() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[&[5, 0], &[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]]));
+            let mut stream =
+                UpgradableAsyncStream::new(stream, Socks5ClientUpgrader::new(("httpbin.org", 80)));
+
+            let err = io::Error::from(stream.upgrade().await.err().unwrap());
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+            assert!(matches!(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<Socks5Error>()),
+                Some(Socks5Error::ConnectionRefused)
+            ));
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct SimpleTlsClientUpgrader {}
+
+    impl<S> TlsClientUpgrader<S> for SimpleTlsClientUpgrader where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+    {
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleTlsClientUpgrader
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[test]
+    fn http_client_proxy() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 0],
+                &[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90],
+                b"foo",
+            ]));
+            let mut stream = HttpClientInnerStream::<_, (), (), (), _>::new(
+                stream,
+                Some(HttpClientProxy::socks5(Socks5ClientUpgrader::new((
+                    "httpbin.org",
+                    80,
+                )))),
+                None,
+            )
+            .await?;
+            assert_eq!(stream.transport().to_string(), "socks5-proxy");
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            //
+            let stream = ScriptedStream::new(replies(&[
+                &[5, 0],
+                &[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90],
+                b"bar",
+            ]));
+            let mut stream = HttpClientInnerStream::<_, (), (), _, _>::new(
+                stream,
+                Some(HttpClientProxy::socks5(Socks5ClientUpgrader::new((
+                    "httpbin.org",
+                    443,
+                )))),
+                Some(SimpleTlsClientUpgrader {}),
+            )
+            .await?;
+            assert_eq!(stream.transport().to_string(), "socks5-proxy+tls");
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "bar");
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,40 @@
+mod common;
+
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod socks5_tokio1_io_tests {
+    use std::io;
+
+    use futures_lite::future::block_on;
+    use tokio1::io::AsyncReadExt;
+
+    use async_stream_packed::socks5::tokio1_io::Socks5ClientUpgrader;
+    use async_stream_packed::UpgradableAsyncStream;
+
+    use super::common::ScriptedStream;
+
+    //
+    //
+    //
+    #[test]
+    fn connect_domain() -> io::Result<()> {
+        block_on(async {
+            let stream = ScriptedStream::new(
+                [&[5, 0][..], &[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90], b"foo"].concat(),
+            );
+            let upgrader = Socks5ClientUpgrader::new(("httpbin.org", 80));
+            let mut stream = UpgradableAsyncStream::new(stream, upgrader);
+            stream.upgrade().await?;
+
+            assert_eq!(
+                stream.get_ref().written,
+                [&[5, 1, 0][..], &[5, 1, 0, 3, 11], b"httpbin.org", &[0, 80]].concat()
+            );
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foo");
+
+            Ok(())
+        })
+    }
+}
@@ -1,4 +1,4 @@
-#[cfg(all(feature = "futures_io", not(feature = "tokio_io")))]
+#[cfg(feature = "futures_io")]
 mod syncable_with_context_futures_io_tests {
     use std::io::{self, BufRead, Read, Seek, Write};
     use std::task::Poll;
@@ -6,7 +6,7 @@ mod syncable_with_context_futures_io_tests {
     use futures_lite::future::{self, block_on};
     use futures_lite::io::Cursor;
 
-    use async_stream_packed::SyncableWithContextAsyncStream;
+    use async_stream_packed::syncable_with_context::futures_io::SyncableWithContextAsyncStream;
 
     #[test]
     fn cursor() -> io::Result<()> {
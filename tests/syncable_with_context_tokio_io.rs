@@ -1,5 +1,5 @@
-#[cfg(all(not(feature = "futures_io"), feature = "tokio_io"))]
+#[cfg(feature = "tokio_io")]
 mod syncable_with_context_tokio_io_tests {
     #![allow(unused_imports)]
-    use async_stream_packed::SyncableWithContextAsyncStream;
+    use async_stream_packed::syncable_with_context::tokio_io::SyncableWithContextAsyncStream;
 }
@@ -1,8 +1,4 @@
-#[cfg(all(
-    feature = "syncable_with_waker",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "syncable_with_waker", feature = "futures_io"))]
 mod syncable_with_waker_futures_io_tests {
     use std::io::{self, Read, Seek, Write};
     use std::task::Poll;
@@ -10,7 +6,9 @@ mod syncable_with_waker_futures_io_tests {
     use futures_lite::future::{self, block_on};
     use futures_lite::io::Cursor;
 
-    use async_stream_packed::{syncable_with_waker::WakerKind, SyncableWithWakerAsyncStream};
+    use async_stream_packed::syncable_with_waker::{
+        futures_io::SyncableWithWakerAsyncStream, WakerKind,
+    };
 
     #[test]
     fn cursor() -> io::Result<()> {
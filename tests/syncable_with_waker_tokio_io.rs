@@ -1,9 +1,7 @@
-#[cfg(all(
-    feature = "syncable_with_waker",
-    not(feature = "futures_io"),
-    feature = "tokio_io"
-))]
+#[cfg(all(feature = "syncable_with_waker", feature = "tokio_io"))]
 mod syncable_with_waker_tokio_io_tests {
     #![allow(unused_imports)]
-    use async_stream_packed::{syncable_with_waker::WakerKind, SyncableWithWakerAsyncStream};
+    use async_stream_packed::syncable_with_waker::{
+        tokio_io::SyncableWithWakerAsyncStream, WakerKind,
+    };
 }
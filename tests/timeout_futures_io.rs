@@ -0,0 +1,158 @@
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
+mod timeout_futures_io_tests {
+    use std::io;
+    use std::time::Duration;
+
+    use async_channel::{unbounded, Receiver};
+    use async_trait::async_trait;
+    use futures_lite::future::{self, block_on};
+    use futures_lite::io::Cursor;
+    use futures_lite::{AsyncRead, AsyncWrite};
+
+    use async_stream_packed::{
+        Downgrader, GradableAsyncStream, TimeoutUpgrader, UpgradableAsyncStream, UpgradeError,
+        Upgrader,
+    };
+
+    //
+    //
+    //
+    struct SimpleGraderWithReceiver {
+        receiver: Receiver<()>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleGraderWithReceiver
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.receiver.recv().await.unwrap();
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl<S> Downgrader<S> for SimpleGraderWithReceiver
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        async fn downgrade(&mut self, output: S) -> io::Result<S> {
+            self.receiver.recv().await.unwrap();
+            Ok(output)
+        }
+    }
+
+    fn expired_timer(_: Duration) -> future::Ready<()> {
+        future::ready(())
+    }
+
+    fn never_timer(_: Duration) -> future::Pending<()> {
+        future::pending()
+    }
+
+    #[test]
+    fn upgrade_with_timeout() -> io::Result<()> {
+        block_on(async {
+            let (sender, receiver) = unbounded();
+
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream =
+                UpgradableAsyncStream::new(cursor, SimpleGraderWithReceiver { receiver });
+
+            let err = stream
+                .upgrade_with_timeout(Duration::from_secs(1), &expired_timer)
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(err, UpgradeError::Timeout));
+            assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+            assert!(stream.is_upgrading());
+
+            sender.send(()).await.unwrap();
+            stream
+                .upgrade_with_timeout(Duration::from_secs(1), &never_timer)
+                .await?;
+            assert!(stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn downgrade_with_timeout() -> io::Result<()> {
+        block_on(async {
+            let (sender, receiver) = unbounded();
+
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = GradableAsyncStream::with_upgraded_stream_and_grader(
+                cursor,
+                SimpleGraderWithReceiver { receiver },
+            );
+
+            let err = stream
+                .downgrade_with_timeout(Duration::from_secs(1), &expired_timer)
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(err, UpgradeError::Timeout));
+            assert!(stream.is_downgrading());
+
+            sender.send(()).await.unwrap();
+            stream
+                .downgrade_with_timeout(Duration::from_secs(1), &never_timer)
+                .await?;
+            assert!(!stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn timeout_upgrader() -> io::Result<()> {
+        block_on(async {
+            let (sender, receiver) = unbounded();
+
+            let upgrader = TimeoutUpgrader::new(
+                SimpleGraderWithReceiver { receiver },
+                expired_timer,
+                Duration::from_secs(1),
+            );
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Timeout));
+            assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+            // The inner upgrader didn't keep the stream, nothing to recover.
+            assert!(stream.is_poisoned());
+            assert!(matches!(
+                stream.upgrade().await.err().unwrap(),
+                UpgradeError::Poisoned
+            ));
+
+            //
+            let (sender_2, receiver) = unbounded();
+            drop(sender);
+
+            let upgrader = TimeoutUpgrader::new(
+                SimpleGraderWithReceiver { receiver },
+                never_timer,
+                Duration::from_secs(1),
+            );
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = UpgradableAsyncStream::new(cursor, upgrader);
+
+            sender_2.send(()).await.unwrap();
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+
+            sender_2.send(()).await.unwrap();
+            stream.downgrade().await?;
+            assert!(!stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+}
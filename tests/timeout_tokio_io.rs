@@ -0,0 +1,5 @@
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
+mod timeout_tokio_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::{TimeoutUpgrader, Timer};
+}
@@ -1,8 +1,4 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod tls_futures_io_tests {
     #![allow(unused_imports)]
     use async_stream_packed::{TlsClientUpgrader, TlsServerUpgrader};
@@ -0,0 +1,5 @@
+#[cfg(all(feature = "upgradable", feature = "tokio1_io"))]
+mod tls_tokio1_io_tests {
+    #![allow(unused_imports)]
+    use async_stream_packed::{TlsClientUpgrader, TlsServerUpgrader};
+}
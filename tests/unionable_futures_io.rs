@@ -1,8 +1,4 @@
-#[cfg(all(
-    feature = "unionable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "unionable", feature = "futures_io"))]
 mod unionable_futures_io_tests {
     use std::io;
     use std::pin::Pin;
@@ -1,9 +1,28 @@
-#[cfg(all(
-    feature = "unionable",
-    not(feature = "futures_io"),
-    feature = "tokio_io"
-))]
+#[cfg(all(feature = "unionable", feature = "tokio_io"))]
 mod unionable_tokio_io_tests {
-    #![allow(unused_imports)]
+    use std::io::{self, Cursor};
+
+    use futures_lite::future::block_on;
+    use tokio::io::{empty, AsyncReadExt};
+
     use async_stream_packed::UnionableAsyncStream;
+
+    #[test]
+    fn sample() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream = if true {
+                UnionableAsyncStream::one(cursor)
+            } else {
+                UnionableAsyncStream::the_other(empty())
+            };
+
+            let mut buf = vec![0u8; 5];
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(n, 3);
+            assert_eq!(buf, b"foo\0\0");
+
+            Ok(())
+        })
+    }
 }
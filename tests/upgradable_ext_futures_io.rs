@@ -1,8 +1,4 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod upgradable_ext_futures_io_tests {
     use std::io;
 
@@ -12,7 +8,7 @@ mod upgradable_ext_futures_io_tests {
     use futures_lite::{AsyncRead, AsyncWrite};
 
     use async_stream_packed::{
-        UpgradableAsyncStream, Upgrader, UpgraderExtIntoStream, UpgraderExtRefer,
+        UpgradableAsyncStream, UpgradeError, Upgrader, UpgraderExtIntoStream, UpgraderExtRefer,
     };
 
     struct SimpleUpgrader {}
@@ -74,6 +70,52 @@ mod upgradable_ext_futures_io_tests {
         })
     }
 
+    struct SimpleUpgraderWithUpgradeFailed {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleUpgraderWithUpgradeFailed
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, _stream: S) -> io::Result<Self::Output> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "foo"))
+        }
+    }
+
+    impl<S> UpgraderExtRefer<S> for SimpleUpgraderWithUpgradeFailed
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        fn get_ref(output: &Self::Output) -> &S {
+            output
+        }
+        fn get_mut(output: &mut Self::Output) -> &mut S {
+            output
+        }
+    }
+
+    #[test]
+    fn try_refer() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream = UpgradableAsyncStream::new(cursor, SimpleUpgraderWithUpgradeFailed {});
+
+            assert_eq!(stream.try_get_ref()?.get_ref(), &b"foo");
+            assert_eq!(stream.try_get_mut()?.get_mut(), &mut b"foo");
+
+            assert!(stream.upgrade().await.is_err());
+            assert!(stream.is_poisoned());
+
+            let err = stream.try_get_ref().err().unwrap();
+            assert!(matches!(err, UpgradeError::Poisoned));
+            let err = stream.try_get_mut().err().unwrap();
+            assert!(matches!(err, UpgradeError::Poisoned));
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn into_stream() -> io::Result<()> {
         block_on(async {
@@ -112,7 +154,7 @@ mod upgradable_ext_futures_io_tests {
     fn try_into_stream() -> io::Result<()> {
         block_on(async {
             let cursor = Cursor::new(b"foo".to_vec());
-            let stream = UpgradableAsyncStream::new(cursor, SimpleUpgrader {});
+            let stream = UpgradableAsyncStream::new(cursor, SimpleUpgraderWithoutIntoStream {});
 
             assert_eq!(stream.try_into_stream()?.get_ref(), &b"foo");
 
@@ -121,8 +163,7 @@ mod upgradable_ext_futures_io_tests {
             let stream = UpgradableAsyncStream::with_upgraded_stream(cursor);
 
             let err = stream.try_into_stream().err().unwrap();
-            assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "unimplemented");
+            assert!(matches!(err, UpgradeError::AlreadyUpgraded));
 
             Ok(())
         })
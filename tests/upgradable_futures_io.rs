@@ -1,17 +1,17 @@
-#[cfg(all(
-    feature = "upgradable",
-    feature = "futures_io",
-    not(feature = "tokio_io")
-))]
+#[cfg(all(feature = "upgradable", feature = "futures_io"))]
 mod upgradable_futures_io_tests {
+    use std::future::Future;
     use std::io;
+    use std::pin::Pin;
+    use std::task::Poll;
 
+    use async_channel::{unbounded, Receiver};
     use async_trait::async_trait;
-    use futures_lite::future::block_on;
+    use futures_lite::future::{self, block_on};
     use futures_lite::io::Cursor;
-    use futures_lite::{AsyncRead, AsyncWrite};
+    use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite};
 
-    use async_stream_packed::{UpgradableAsyncStream, Upgrader};
+    use async_stream_packed::{UpgradableAsyncStream, UpgradeError, Upgrader};
 
     //
     //
@@ -34,11 +34,11 @@ mod upgradable_futures_io_tests {
         block_on(async {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream = UpgradableAsyncStream::new(cursor, SimpleUpgrader {});
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), true);
+            assert!(!stream.is_upgraded());
+            assert!(stream.upgrade_required());
             stream.upgrade().await?;
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(stream.is_upgraded());
+            assert!(!stream.upgrade_required());
 
             Ok(())
         })
@@ -49,8 +49,8 @@ mod upgradable_futures_io_tests {
         block_on(async {
             let cursor = Cursor::new(Vec::<u8>::new());
             let stream = UpgradableAsyncStream::new(cursor, ());
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(!stream.upgrade_required());
 
             Ok(())
         })
@@ -61,8 +61,8 @@ mod upgradable_futures_io_tests {
         block_on(async {
             let cursor = Cursor::new(Vec::<u8>::new());
             let stream = UpgradableAsyncStream::with_upgraded_stream(cursor);
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(stream.is_upgraded());
+            assert!(!stream.upgrade_required());
 
             Ok(())
         })
@@ -93,20 +93,193 @@ mod upgradable_futures_io_tests {
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream =
                 UpgradableAsyncStream::new(cursor, SimpleUpgraderWithNotUpgradeRequired {});
-            assert_eq!(stream.is_upgraded(), false);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(!stream.is_upgraded());
+            assert!(!stream.upgrade_required());
             let err = stream.upgrade().await.err().unwrap();
-            assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "upgrade not required");
+            assert!(matches!(err, UpgradeError::UpgradeNotRequired));
+            assert!(!stream.is_upgraded());
 
             //
             let cursor = Cursor::new(Vec::<u8>::new());
             let mut stream = UpgradableAsyncStream::with_upgraded_stream(cursor);
-            assert_eq!(stream.is_upgraded(), true);
-            assert_eq!(stream.upgrade_required(), false);
+            assert!(stream.is_upgraded());
+            assert!(!stream.upgrade_required());
             let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::AlreadyUpgraded));
+            assert!(stream.is_upgraded());
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct SimpleUpgraderWithUpgradeFailed {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleUpgraderWithUpgradeFailed
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, _stream: S) -> io::Result<Self::Output> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "foo"))
+        }
+    }
+
+    #[test]
+    fn upgrade_failed() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = UpgradableAsyncStream::new(cursor, SimpleUpgraderWithUpgradeFailed {});
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Upgrader(_)));
+            assert!(stream.is_poisoned());
+            assert!(!stream.is_upgraded());
+            assert!(!stream.upgrade_required());
+
+            let err_poisoned = stream.read(&mut [0u8; 1]).await.err().unwrap();
+            assert_eq!(err_poisoned.kind(), io::ErrorKind::Other);
+            assert_eq!(err_poisoned.to_string(), "poisoned");
+
+            let err_poisoned = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err_poisoned, UpgradeError::Poisoned));
+
+            let err: io::Error = err.into();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+            assert_eq!(err.to_string(), "foo");
+
+            let err: io::Error = UpgradeError::AlreadyUpgraded.into();
             assert_eq!(err.kind(), io::ErrorKind::Other);
-            assert_eq!(err.to_string(), "not allow");
+            assert_eq!(err.to_string(), "already upgraded");
+
+            let err: UpgradeError = err.into();
+            assert!(matches!(err, UpgradeError::AlreadyUpgraded));
+
+            let err: io::Error = UpgradeError::Timeout.into();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            let err: UpgradeError = err.into();
+            assert!(matches!(err, UpgradeError::Timeout));
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    #[derive(Default)]
+    struct SimpleUpgraderWithRecoverStream<S> {
+        upgrade_count: usize,
+        stream: Option<S>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleUpgraderWithRecoverStream<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.upgrade_count += 1;
+            match self.upgrade_count {
+                1 => {
+                    self.stream = Some(stream);
+                    Err(io::Error::new(io::ErrorKind::TimedOut, "foo"))
+                }
+                _ => Ok(stream),
+            }
+        }
+        fn recover_stream(&mut self) -> Option<S> {
+            self.stream.take()
+        }
+    }
+
+    #[test]
+    fn upgrade_failed_and_recover_stream() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream =
+                UpgradableAsyncStream::new(cursor, SimpleUpgraderWithRecoverStream::default());
+            let err = stream.upgrade().await.err().unwrap();
+            assert!(matches!(err, UpgradeError::Upgrader(_)));
+            assert!(!stream.is_poisoned());
+            assert!(!stream.is_upgraded());
+            assert!(stream.upgrade_required());
+
+            stream.upgrade().await?;
+            assert!(stream.is_upgraded());
+
+            let mut buf = vec![0u8; 3];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(buf, b"foo");
+
+            Ok(())
+        })
+    }
+
+    //
+    //
+    //
+    struct SimpleUpgraderWithTwoAwaitPoints {
+        receiver: Receiver<()>,
+    }
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleUpgraderWithTwoAwaitPoints
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            self.receiver.recv().await.unwrap();
+            self.receiver.recv().await.unwrap();
+            Ok(stream)
+        }
+    }
+
+    #[test]
+    fn upgrade_future_dropped() -> io::Result<()> {
+        block_on(async {
+            let (sender, receiver) = unbounded();
+
+            let cursor = Cursor::new(b"foo".to_vec());
+            let mut stream =
+                UpgradableAsyncStream::new(cursor, SimpleUpgraderWithTwoAwaitPoints { receiver });
+
+            // dropped at the first await point
+            future::poll_fn(|cx| {
+                let mut upgrade = stream.upgrade();
+                assert!(Pin::new(&mut upgrade).poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            assert!(stream.is_upgrading());
+            assert!(!stream.is_upgraded());
+            assert!(!stream.upgrade_required());
+
+            let err = stream.read(&mut [0u8; 1]).await.err().unwrap();
+            assert_eq!(err.to_string(), "upgrade or downgrade in progress");
+
+            // dropped at the second await point
+            sender.send(()).await.unwrap();
+            future::poll_fn(|cx| {
+                assert!(stream.poll_upgrade(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            assert!(stream.is_upgrading());
+
+            // resumed
+            sender.send(()).await.unwrap();
+            stream.upgrade().await?;
+            assert!(!stream.is_upgrading());
+            assert!(stream.is_upgraded());
+
+            let mut buf = vec![0u8; 3];
+            stream.read_exact(&mut buf).await?;
+            assert_eq!(buf, b"foo");
 
             Ok(())
         })
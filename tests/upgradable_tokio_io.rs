@@ -1,9 +1,48 @@
-#[cfg(all(
-    feature = "upgradable",
-    not(feature = "futures_io"),
-    feature = "tokio_io"
-))]
+#[cfg(all(feature = "upgradable", feature = "tokio_io"))]
 mod upgradable_tokio_io_tests {
-    #![allow(unused_imports)]
+    use std::io::{self, Cursor};
+
+    use async_trait::async_trait;
+    use futures_lite::future::block_on;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
     use async_stream_packed::{UpgradableAsyncStream, Upgrader};
+
+    //
+    //
+    //
+    struct SimpleUpgrader {}
+
+    #[async_trait]
+    impl<S> Upgrader<S> for SimpleUpgrader
+    where
+        S: Send + 'static,
+    {
+        type Output = S;
+        async fn upgrade(&mut self, stream: S) -> io::Result<Self::Output> {
+            Ok(stream)
+        }
+    }
+
+    #[test]
+    fn read_and_write() -> io::Result<()> {
+        block_on(async {
+            let cursor = Cursor::new(Vec::<u8>::new());
+            let mut stream = UpgradableAsyncStream::new(cursor, SimpleUpgrader {});
+
+            stream.write_all(b"foo").await?;
+            stream.upgrade().await?;
+            stream.write_all(b"bar").await?;
+
+            let mut cursor = stream.try_into_upgraded_stream()?;
+            cursor.set_position(0);
+            let mut stream = UpgradableAsyncStream::with_upgraded_stream(cursor);
+
+            let mut buf = String::new();
+            stream.read_to_string(&mut buf).await?;
+            assert_eq!(buf, "foobar");
+
+            Ok(())
+        })
+    }
 }